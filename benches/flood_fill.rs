@@ -0,0 +1,66 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+use color_it::generate::{generate, Pattern};
+use color_it::greedy::{self, GreedyScore};
+use color_it::{Budget, Flood};
+
+const SIZES: [usize; 3] = [14, 26, 260];
+
+/// Floods a uniform random grid with every colour in turn, which walks the
+/// flooded region once per move as it grows.
+fn flood_fill(c: &mut Criterion) {
+    let mut group = c.benchmark_group("flood_fill");
+    for size in SIZES {
+        let grid = generate(size, size, 6, Pattern::Uniform, 1);
+        group.bench_with_input(BenchmarkId::from_parameter(format!("{0}x{0}", size)), &grid, |b, grid| {
+            b.iter_batched(
+                || grid.clone(),
+                |mut grid| {
+                    for color in 0..6 {
+                        grid.flood_fill(color);
+                    }
+                    grid
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// Replays a whole greedy solution with `flood_fill` and with the
+/// incremental `Flood`.
+fn replay(c: &mut Criterion) {
+    let mut group = c.benchmark_group("replay");
+    group.sample_size(10);
+    for size in SIZES {
+        let grid = generate(size, size, 6, Pattern::Uniform, 1);
+        let moves = greedy::solve_greedy(&grid, GreedyScore::Cells, &Budget::unlimited());
+        let name = format!("{0}x{0}", size);
+        group.bench_with_input(BenchmarkId::new("flood_fill", &name), &grid, |b, grid| {
+            b.iter_batched(
+                || grid.clone(),
+                |mut grid| {
+                    for &color in &moves {
+                        grid.flood_fill(color);
+                    }
+                    grid
+                },
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("incremental", &name), &grid, |b, grid| {
+            b.iter(|| {
+                let mut flood = Flood::new(grid);
+                for &color in &moves {
+                    flood.play(color);
+                }
+                flood
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, flood_fill, replay);
+criterion_main!(benches);
@@ -0,0 +1,298 @@
+//! Computer players for two-player Flood-It: greedy, random, alpha-beta
+//! minimax and Monte Carlo tree search.
+
+use crate::duel::{Agent, Duel, Player, PLIES_PER_CELL};
+use crate::rng::Rng;
+
+/// Engine names accepted by `engine`.
+pub const ENGINE_NAMES: [&str; 4] = ["greedy", "random", "minimax", "mcts"];
+
+/// Builds an engine from a spec: a name from `ENGINE_NAMES`, optionally
+/// followed by `:` and the search depth of `minimax` (4 by default) or the
+/// iterations of `mcts` (1000 by default). `seed` drives the random ones.
+pub fn engine(spec: &str, seed: u64) -> Result<Box<dyn Agent>, String> {
+    let (name, param) = match spec.split_once(':') {
+        Some((name, param)) => {
+            let param = param.parse::<usize>().map_err(|_| format!("invalid parameter {:?} in {:?}", param, spec))?;
+            (name, Some(param))
+        }
+        None => (spec, None),
+    };
+    match (name, param) {
+        ("greedy", None) => Ok(Box::new(Greedy)),
+        ("random", None) => Ok(Box::new(RandomMover::new(seed))),
+        ("minimax", depth) => Ok(Box::new(Minimax { depth: depth.unwrap_or(4).max(1) })),
+        ("mcts", iterations) => Ok(Box::new(Mcts::new(iterations.unwrap_or(1000).max(1), seed))),
+        ("greedy" | "random", Some(_)) => Err(format!("{} takes no parameter", name)),
+        _ => Err(format!("unknown engine {:?}, expected one of {}", name, ENGINE_NAMES.join(", "))),
+    }
+}
+
+/// Legal moves with the position each leads to and the cells it absorbs,
+/// largest gain first, then lowest colour.
+fn children(duel: &Duel) -> Vec<(u8, Duel, usize)> {
+    let mut children: Vec<(u8, Duel, usize)> = duel
+        .legal_moves()
+        .into_iter()
+        .map(|color| {
+            let mut next = duel.clone();
+            let absorbed = next.play(color);
+            (color, next, absorbed)
+        })
+        .collect();
+    children.sort_by_key(|&(color, _, absorbed)| (std::cmp::Reverse(absorbed), color));
+    children
+}
+
+/// Takes the move absorbing the most cells.
+pub struct Greedy;
+
+impl Agent for Greedy {
+    fn name(&self) -> String {
+        "greedy".to_string()
+    }
+
+    fn choose(&mut self, duel: &Duel) -> Option<u8> {
+        children(duel).first().map(|&(color, _, _)| color)
+    }
+}
+
+/// Plays a uniformly random legal move.
+pub struct RandomMover {
+    rng: Rng,
+}
+
+impl RandomMover {
+    pub fn new(seed: u64) -> Self {
+        RandomMover { rng: Rng::new(seed) }
+    }
+}
+
+impl Agent for RandomMover {
+    fn name(&self) -> String {
+        "random".to_string()
+    }
+
+    fn choose(&mut self, duel: &Duel) -> Option<u8> {
+        let moves = duel.legal_moves();
+        Some(moves[self.rng.below(moves.len())])
+    }
+}
+
+/// Depth-limited negamax with alpha-beta pruning. Positions are scored by the
+/// difference in owned cells; decided games, where the lead exceeds the
+/// unowned cells, outrank any undecided one.
+pub struct Minimax {
+    pub depth: usize,
+}
+
+const WIN: i64 = 1 << 40;
+
+impl Minimax {
+    /// Value of `duel` for the player to move.
+    fn negamax(duel: &Duel, depth: usize, mut alpha: i64, beta: i64) -> i64 {
+        let me = duel.to_move();
+        let diff = duel.owned(me) as i64 - duel.owned(me.other()) as i64;
+        let unowned = (duel.open_cells() - duel.owned(me) - duel.owned(me.other())) as i64;
+        if duel.is_over() || diff.abs() > unowned {
+            return diff.signum() * WIN + diff;
+        }
+        if depth == 0 {
+            return diff;
+        }
+        let mut best = i64::MIN;
+        for (_, child, _) in children(duel) {
+            best = best.max(-Self::negamax(&child, depth - 1, -beta, -alpha));
+            alpha = alpha.max(best);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+}
+
+impl Agent for Minimax {
+    fn name(&self) -> String {
+        format!("minimax:{}", self.depth)
+    }
+
+    fn choose(&mut self, duel: &Duel) -> Option<u8> {
+        let mut best = None;
+        let mut alpha = -i64::MAX;
+        for (color, child, _) in children(duel) {
+            let value = -Self::negamax(&child, self.depth - 1, -i64::MAX, -alpha);
+            if best.is_none() || value > alpha {
+                best = Some(color);
+                alpha = value;
+            }
+        }
+        best
+    }
+}
+
+/// Upper-confidence tree search with playouts that favour growing moves.
+pub struct Mcts {
+    pub iterations: usize,
+    /// UCT exploration constant.
+    pub exploration: f64,
+    rng: Rng,
+}
+
+struct Node {
+    parent: usize,
+    color: u8,
+    /// Player who played `color`.
+    mover: Player,
+    children: Vec<usize>,
+    untried: Vec<u8>,
+    visits: u32,
+    /// Sum of the playout rewards for `mover`.
+    value: f64,
+}
+
+/// Weight of the cell share in a playout reward, the rest going to the
+/// result. Without it a winning engine has no reason to finish the game.
+const SHARE_WEIGHT: f64 = 0.1;
+
+impl Mcts {
+    pub fn new(iterations: usize, seed: u64) -> Self {
+        Mcts { iterations, exploration: std::f64::consts::SQRT_2, rng: Rng::new(seed) }
+    }
+
+    fn select(&self, nodes: &[Node], id: usize) -> usize {
+        let log_visits = (nodes[id].visits as f64).ln();
+        let uct = |c: usize| {
+            let child = &nodes[c];
+            child.value / child.visits as f64 + self.exploration * (log_visits / child.visits as f64).sqrt()
+        };
+        let children = &nodes[id].children;
+        children.iter().copied().fold(children[0], |best, c| if uct(c) > uct(best) { c } else { best })
+    }
+
+    /// Finishes the game with random moves that grow the mover's region
+    /// when one can, and returns the final position.
+    fn playout(&mut self, mut duel: Duel) -> Duel {
+        let limit = PLIES_PER_CELL * duel.open_cells();
+        while !duel.is_over() && duel.plies() < limit {
+            let growing: Vec<u8> = duel.frontier_colors(duel.to_move()).into_iter().filter(|&c| duel.is_legal(c)).collect();
+            let moves = if growing.is_empty() { duel.legal_moves() } else { growing };
+            duel.play(moves[self.rng.below(moves.len())]);
+        }
+        duel
+    }
+
+    /// Reward of a finished playout for `player`: 1 for a win, half for a
+    /// draw, blended with the share of the cells they own.
+    fn reward(end: &Duel, player: Player) -> f64 {
+        let result = match end.leader() {
+            Some(leader) if leader == player => 1.0,
+            Some(_) => 0.0,
+            None => 0.5,
+        };
+        let share = end.owned(player) as f64 / end.open_cells() as f64;
+        (1.0 - SHARE_WEIGHT) * result + SHARE_WEIGHT * share
+    }
+}
+
+impl Agent for Mcts {
+    fn name(&self) -> String {
+        format!("mcts:{}", self.iterations)
+    }
+
+    fn choose(&mut self, duel: &Duel) -> Option<u8> {
+        let moves = duel.legal_moves();
+        if moves.len() == 1 {
+            return Some(moves[0]);
+        }
+        let root = Node {
+            parent: usize::MAX,
+            color: 0,
+            mover: duel.to_move().other(),
+            children: Vec::new(),
+            untried: moves,
+            visits: 0,
+            value: 0.0,
+        };
+        let mut nodes = vec![root];
+        for _ in 0..self.iterations {
+            let mut state = duel.clone();
+            let mut id = 0;
+            while nodes[id].untried.is_empty() && !nodes[id].children.is_empty() {
+                id = self.select(&nodes, id);
+                state.play(nodes[id].color);
+            }
+            if !nodes[id].untried.is_empty() {
+                let pick = self.rng.below(nodes[id].untried.len());
+                let color = nodes[id].untried.swap_remove(pick);
+                let mover = state.to_move();
+                state.play(color);
+                let untried = if state.is_over() { Vec::new() } else { state.legal_moves() };
+                nodes.push(Node { parent: id, color, mover, children: Vec::new(), untried, visits: 0, value: 0.0 });
+                let child = nodes.len() - 1;
+                nodes[id].children.push(child);
+                id = child;
+            }
+            let end = self.playout(state);
+            while id != usize::MAX {
+                let node = &mut nodes[id];
+                node.visits += 1;
+                node.value += Self::reward(&end, node.mover);
+                id = node.parent;
+            }
+        }
+        nodes[0].children.iter().max_by_key(|&&c| nodes[c].visits).map(|&c| nodes[c].color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duel::run_match;
+    use crate::generate::{generate, Pattern};
+    use crate::Grid;
+
+    #[test]
+    fn test_engine_specs() {
+        assert_eq!(engine("minimax:3", 0).unwrap().name(), "minimax:3");
+        assert_eq!(engine("mcts", 0).unwrap().name(), "mcts:1000");
+        assert!(engine("greedy:2", 0).is_err());
+        assert!(engine("alphazero", 0).is_err());
+        assert!(engine("minimax:deep", 0).is_err());
+    }
+
+    #[test]
+    fn test_engines_play_legal_moves() {
+        let duel = Duel::new(&generate(8, 8, 5, Pattern::Uniform, 2)).unwrap();
+        for name in ENGINE_NAMES {
+            let mut agent = engine(&format!("{}{}", name, if name == "mcts" { ":200" } else { "" }), 3).unwrap();
+            let color = agent.choose(&duel).unwrap();
+            assert!(duel.is_legal(color), "{} played {}", name, color);
+        }
+        let mut a = Mcts::new(200, 5);
+        let mut b = Mcts::new(200, 5);
+        assert_eq!(a.choose(&duel), b.choose(&duel));
+    }
+
+    #[test]
+    fn test_one_ply_minimax_is_greedy() {
+        let mut duel = Duel::new(&generate(9, 9, 5, Pattern::Clusters { patch_size: 3 }, 4)).unwrap();
+        while !duel.is_over() {
+            let color = Greedy.choose(&duel).unwrap();
+            assert_eq!(Minimax { depth: 1 }.choose(&duel), Some(color));
+            let best = duel.legal_moves().into_iter().map(|c| duel.clone().play(c)).max().unwrap();
+            assert_eq!(duel.play(color), best);
+        }
+    }
+
+    #[test]
+    fn test_search_beats_random_play() {
+        let grids: Vec<Grid> = (0..3).map(|seed| generate(6, 6, 4, Pattern::Uniform, seed)).collect();
+        for mut engine in [engine("minimax:3", 0).unwrap(), engine("mcts:100", 0).unwrap()] {
+            let mut random = RandomMover::new(1);
+            let report = run_match(&grids, [engine.as_mut(), &mut random], &mut |_, _, _| {}).unwrap();
+            assert_eq!(report.games, 6);
+            assert!(report.wins[0] > report.wins[1] + report.draws, "{}", report);
+        }
+    }
+}
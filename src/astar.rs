@@ -0,0 +1,229 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::bound::Bounds;
+use crate::budget::Budget;
+use crate::greedy::{self, GreedyScore};
+use crate::region::{FloodState, RegionGraph};
+use crate::table::{TableStats, TranspositionTable};
+use crate::Grid;
+
+/// Outcome of an optimal search.
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub moves: Vec<u8>,
+    pub proven_optimal: bool,
+    pub nodes_expanded: usize,
+    pub table: TableStats,
+}
+
+/// Approximate memory held by one stored flood state: two bitsets and the
+/// per-colour frontier buckets and counters. Bucket contents are not counted.
+pub(crate) fn state_bytes(graph: &RegionGraph) -> usize {
+    let per_color = std::mem::size_of::<Vec<usize>>() + std::mem::size_of::<usize>();
+    std::mem::size_of::<FloodState>() + 2 * graph.component_count().div_ceil(64) * 8 + graph.colors * per_color
+}
+
+/// A* over flood states ordered by `moves + Bounds::value`.
+///
+/// The bounds are consistent (a single move lowers each of them by at most
+/// one), so the first complete state popped from the queue is optimal. The
+/// greedy solution is kept as an incumbent: it prunes every node that cannot
+/// beat it and is returned if the budget runs out.
+pub fn solve_astar(
+    grid: &Grid,
+    table_bytes: usize,
+    budget: &Budget,
+    on_improve: &mut dyn FnMut(&[u8]),
+) -> SearchResult {
+    struct Node {
+        parent: usize,
+        color: u8,
+        depth: usize,
+        state: FloodState,
+    }
+
+    let graph = RegionGraph::new(grid);
+    let root = graph.initial_state();
+    let incumbent = greedy::complete_greedily(&graph, &root, GreedyScore::Cells, budget);
+    on_improve(&incumbent);
+
+    let mut best_depth = TranspositionTable::new(table_bytes, graph.component_count());
+    let mut open = BinaryHeap::new();
+    let mut nodes_expanded = 0;
+    let node_bytes = state_bytes(&graph) + std::mem::size_of::<Node>() + 16;
+
+    best_depth.store(root.clone(), 0);
+    open.push(Reverse((Bounds::compute(&graph, &root).value(), 0usize)));
+    let mut nodes = vec![Node { parent: usize::MAX, color: 0, depth: 0, state: root }];
+
+    while let Some(Reverse((_, id))) = open.pop() {
+        let depth = nodes[id].depth;
+        if best_depth.probe(&nodes[id].state).is_some_and(|d| d < depth) {
+            continue;
+        }
+
+        if graph.is_complete(&nodes[id].state) {
+            let mut moves = Vec::with_capacity(depth);
+            let mut cursor = id;
+            while cursor != 0 {
+                moves.push(nodes[cursor].color);
+                cursor = nodes[cursor].parent;
+            }
+            moves.reverse();
+            on_improve(&moves);
+            return SearchResult { moves, proven_optimal: true, nodes_expanded, table: best_depth.stats() };
+        }
+
+        if budget.expand() || budget.exceeds_memory(nodes.len() * node_bytes + best_depth.stats().bytes) {
+            return SearchResult { moves: incumbent, proven_optimal: false, nodes_expanded, table: best_depth.stats() };
+        }
+
+        nodes_expanded += 1;
+        for color in graph.frontier_colors(&nodes[id].state) {
+            let next = graph.play(&nodes[id].state, color);
+            if best_depth.probe(&next).is_some_and(|d| d <= depth + 1) {
+                continue;
+            }
+
+            let f = depth + 1 + Bounds::compute(&graph, &next).value();
+            if f >= incumbent.len() {
+                continue;
+            }
+            best_depth.store(next.clone(), depth + 1);
+            open.push(Reverse((f, nodes.len())));
+            nodes.push(Node { parent: id, color, depth: depth + 1, state: next });
+        }
+    }
+
+    // Nothing shorter than the incumbent exists.
+    SearchResult { moves: incumbent, proven_optimal: true, nodes_expanded, table: best_depth.stats() }
+}
+
+/// Iterative deepening A*: same bounds and incumbent as `solve_astar`, with a
+/// bounded transposition table that is cleared between iterations.
+pub fn solve_ida(
+    grid: &Grid,
+    table_bytes: usize,
+    budget: &Budget,
+    on_improve: &mut dyn FnMut(&[u8]),
+) -> SearchResult {
+    struct Search<'a> {
+        graph: &'a RegionGraph,
+        budget: &'a Budget,
+        table: TranspositionTable,
+        moves: Vec<u8>,
+        nodes_expanded: usize,
+    }
+
+    impl Search<'_> {
+        /// `Ok` once a solution is in `moves`, otherwise the smallest f-value
+        /// above `threshold` (`usize::MAX` when the budget ran out).
+        fn run(&mut self, state: &FloodState, threshold: usize) -> Result<(), usize> {
+            let f = self.moves.len() + Bounds::compute(self.graph, state).value();
+            if f > threshold {
+                return Err(f);
+            }
+            if self.graph.is_complete(state) {
+                return Ok(());
+            }
+            // A state already searched this iteration at the same or a smaller
+            // depth cannot lead to a solution within the threshold.
+            if self.table.probe(state).is_some_and(|d| d <= self.moves.len()) {
+                return Err(usize::MAX);
+            }
+            if self.budget.expand() {
+                return Err(usize::MAX);
+            }
+            self.table.store(state.clone(), self.moves.len());
+
+            self.nodes_expanded += 1;
+            let mut next_threshold = usize::MAX;
+            for color in self.graph.frontier_colors(state) {
+                self.moves.push(color);
+                match self.run(&self.graph.play(state, color), threshold) {
+                    Ok(()) => return Ok(()),
+                    Err(t) => next_threshold = next_threshold.min(t),
+                }
+                self.moves.pop();
+                if self.budget.stop_reason().is_some() {
+                    break;
+                }
+            }
+            Err(next_threshold)
+        }
+    }
+
+    let graph = RegionGraph::new(grid);
+    let root = graph.initial_state();
+    let incumbent = greedy::complete_greedily(&graph, &root, GreedyScore::Cells, budget);
+    on_improve(&incumbent);
+
+    let mut search = Search {
+        graph: &graph,
+        budget,
+        table: TranspositionTable::new(table_bytes, graph.component_count()),
+        moves: Vec::new(),
+        nodes_expanded: 0,
+    };
+    let mut threshold = Bounds::compute(&graph, &root).value();
+
+    while threshold < incumbent.len() {
+        search.table.clear();
+        match search.run(&root, threshold) {
+            Ok(()) => {
+                on_improve(&search.moves);
+                return SearchResult {
+                    moves: search.moves,
+                    proven_optimal: true,
+                    nodes_expanded: search.nodes_expanded,
+                    table: search.table.stats(),
+                };
+            }
+            Err(_) if budget.stop_reason().is_some() => {
+                return SearchResult {
+                    moves: incumbent,
+                    proven_optimal: false,
+                    nodes_expanded: search.nodes_expanded,
+                    table: search.table.stats(),
+                };
+            }
+            Err(t) => threshold = t,
+        }
+    }
+
+    // Every line shorter than the incumbent has been ruled out.
+    SearchResult { moves: incumbent, proven_optimal: true, nodes_expanded: search.nodes_expanded, table: search.table.stats() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::DEFAULT_TABLE_BYTES;
+
+    #[test]
+    fn test_astar_is_optimal_on_sample() {
+        let grid = Grid::from_csv("1,2,0,0\n0,1,1,0\n2,2,0,1\n0,0,0,1").unwrap();
+        let result = solve_astar(&grid, DEFAULT_TABLE_BYTES, &Budget::unlimited(), &mut |_| {});
+        assert!(result.proven_optimal);
+        assert_eq!(result.moves.len(), 4);
+        assert!(grid.clone().apply_solution(&result.moves));
+    }
+
+    #[test]
+    fn test_ida_matches_astar() {
+        let grid = Grid::from_csv("2,1,3,0,4\n1,2,2,3,1\n0,3,1,2,4\n4,1,0,3,2\n3,2,4,1,0").unwrap();
+        let astar = solve_astar(&grid, DEFAULT_TABLE_BYTES, &Budget::unlimited(), &mut |_| {});
+        let ida = solve_ida(&grid, DEFAULT_TABLE_BYTES, &Budget::unlimited(), &mut |_| {});
+        assert!(ida.proven_optimal);
+        assert_eq!(astar.moves.len(), ida.moves.len());
+        assert!(grid.clone().apply_solution(&ida.moves));
+    }
+
+    #[test]
+    fn test_complete_grid_needs_no_moves() {
+        let grid = Grid::from_csv("3,3\n3,3").unwrap();
+        assert!(solve_astar(&grid, DEFAULT_TABLE_BYTES, &Budget::unlimited(), &mut |_| {}).moves.is_empty());
+        assert!(solve_ida(&grid, DEFAULT_TABLE_BYTES, &Budget::unlimited(), &mut |_| {}).moves.is_empty());
+    }
+}
@@ -0,0 +1,255 @@
+//! Runs registered strategies over a suite of grids and compares them.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::budget::StopReason;
+use crate::solver::{Registry, SolverOptions};
+use crate::Grid;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+/// Global allocator that tracks the peak number of live bytes. The binary
+/// installs it with the `peak-memory` feature to get peak memory in
+/// benchmark records; without it they report zero.
+pub struct PeakAlloc;
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let now = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(now, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+/// Resets the peak to the current allocation and returns it.
+fn reset_peak() -> usize {
+    let now = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(now, Ordering::Relaxed);
+    now
+}
+
+/// One grid of the suite.
+#[derive(Debug, Clone)]
+pub struct Case {
+    pub name: String,
+    pub grid: Grid,
+}
+
+/// Outcome of one strategy on one case.
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub case: String,
+    pub strategy: &'static str,
+    pub moves: usize,
+    pub proven_optimal: bool,
+    pub stopped: Option<StopReason>,
+    pub nodes_expanded: usize,
+    /// Bytes allocated at the peak of the solve, beyond what was live before.
+    pub peak_bytes: usize,
+    pub elapsed: Duration,
+}
+
+/// Solves every case with every strategy in `strategies`, one at a time.
+pub fn run(
+    registry: &Registry,
+    strategies: &[&str],
+    cases: &[Case],
+    options: &SolverOptions,
+    on_record: &mut dyn FnMut(&Record),
+) -> Vec<Record> {
+    let mut records = Vec::new();
+    for case in cases {
+        for &name in strategies {
+            let solver = registry.get(name).unwrap_or_else(|| panic!("unknown strategy {}", name));
+            let baseline = reset_peak();
+            let solution = solver.solve(&case.grid, options, &mut |_| {});
+            let record = Record {
+                case: case.name.clone(),
+                strategy: solver.name(),
+                moves: solution.moves.len(),
+                proven_optimal: solution.proven_optimal,
+                stopped: solution.stopped,
+                nodes_expanded: solution.nodes_expanded,
+                peak_bytes: PEAK.load(Ordering::Relaxed).saturating_sub(baseline),
+                elapsed: solution.elapsed,
+            };
+            assert!(case.grid.is_solution(&solution.moves), "{} returned an invalid solution on {}", name, case.name);
+            on_record(&record);
+            records.push(record);
+        }
+    }
+    records
+}
+
+/// Shortest solution found for each case by any strategy.
+pub fn best_known(records: &[Record]) -> HashMap<&str, usize> {
+    let mut best = HashMap::new();
+    for record in records {
+        let entry = best.entry(record.case.as_str()).or_insert(usize::MAX);
+        *entry = (*entry).min(record.moves);
+    }
+    best
+}
+
+fn stopped_label(stopped: Option<StopReason>) -> String {
+    stopped.map_or_else(String::new, |reason| reason.to_string())
+}
+
+pub fn to_csv(records: &[Record]) -> String {
+    let best = best_known(records);
+    let mut output = String::from("case,strategy,moves,gap,proven_optimal,stopped,nodes_expanded,peak_bytes,seconds\n");
+    for r in records {
+        let _ = writeln!(
+            output,
+            "{},{},{},{},{},{},{},{},{:.6}",
+            r.case,
+            r.strategy,
+            r.moves,
+            r.moves - best[r.case.as_str()],
+            r.proven_optimal,
+            stopped_label(r.stopped),
+            r.nodes_expanded,
+            r.peak_bytes,
+            r.elapsed.as_secs_f64(),
+        );
+    }
+    output
+}
+
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+pub fn to_json(records: &[Record]) -> String {
+    let best = best_known(records);
+    let mut output = String::from("[\n");
+    for (i, r) in records.iter().enumerate() {
+        let stopped = r.stopped.map_or_else(|| "null".to_string(), |reason| json_string(&reason.to_string()));
+        let _ = write!(
+            output,
+            "  {{\"case\": {}, \"strategy\": {}, \"moves\": {}, \"gap\": {}, \"proven_optimal\": {}, \"stopped\": {}, \
+             \"nodes_expanded\": {}, \"peak_bytes\": {}, \"seconds\": {:.6}}}",
+            json_string(&r.case),
+            json_string(r.strategy),
+            r.moves,
+            r.moves - best[r.case.as_str()],
+            r.proven_optimal,
+            stopped,
+            r.nodes_expanded,
+            r.peak_bytes,
+            r.elapsed.as_secs_f64(),
+        );
+        output.push_str(if i + 1 < records.len() { ",\n" } else { "\n" });
+    }
+    output.push_str("]\n");
+    output
+}
+
+/// One line per strategy, in the order they were run: total moves, how many
+/// cases it matched the best known solution on, mean and worst gap, peak
+/// memory and total time.
+pub fn summary(records: &[Record]) -> String {
+    let best = best_known(records);
+    let mut strategies: Vec<&'static str> = Vec::new();
+    for record in records {
+        if !strategies.contains(&record.strategy) {
+            strategies.push(record.strategy);
+        }
+    }
+
+    let mut output = format!(
+        "{:<14} {:>6} {:>8} {:>6} {:>9} {:>8} {:>8} {:>12} {:>11} {:>10}\n",
+        "strategy", "cases", "moves", "best", "mean gap", "max gap", "optimal", "nodes", "peak KiB", "time"
+    );
+    for strategy in strategies {
+        let runs: Vec<&Record> = records.iter().filter(|r| r.strategy == strategy).collect();
+        let gaps: Vec<usize> = runs.iter().map(|r| r.moves - best[r.case.as_str()]).collect();
+        let _ = writeln!(
+            output,
+            "{:<14} {:>6} {:>8} {:>6} {:>9.2} {:>8} {:>8} {:>12} {:>11.1} {:>10.3?}",
+            strategy,
+            runs.len(),
+            runs.iter().map(|r| r.moves).sum::<usize>(),
+            gaps.iter().filter(|&&gap| gap == 0).count(),
+            gaps.iter().sum::<usize>() as f64 / runs.len() as f64,
+            gaps.iter().max().copied().unwrap_or(0),
+            runs.iter().filter(|r| r.proven_optimal).count(),
+            runs.iter().map(|r| r.nodes_expanded).sum::<usize>(),
+            runs.iter().map(|r| r.peak_bytes).max().unwrap_or(0) as f64 / 1024.0,
+            runs.iter().map(|r| r.elapsed).sum::<Duration>(),
+        );
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{generate, Pattern};
+
+    fn cases() -> Vec<Case> {
+        (0..2)
+            .map(|seed| Case { name: format!("seed-{}", seed), grid: generate(5, 5, 3, Pattern::Uniform, seed) })
+            .collect()
+    }
+
+    #[test]
+    fn test_records_and_gaps() {
+        let registry = Registry::default();
+        let mut seen = 0;
+        let records = run(&registry, &["greedy", "astar"], &cases(), &SolverOptions::default(), &mut |_| seen += 1);
+        assert_eq!((records.len(), seen), (4, 4));
+
+        let best = best_known(&records);
+        for record in records.iter().filter(|r| r.strategy == "astar") {
+            assert_eq!(record.moves, best[record.case.as_str()]);
+        }
+        let csv = to_csv(&records);
+        assert_eq!(csv.lines().count(), 5);
+        assert!(csv.lines().nth(2).unwrap().starts_with("seed-0,astar,"));
+        assert!(summary(&records).lines().nth(2).unwrap().starts_with("astar "));
+    }
+
+    #[test]
+    fn test_json_escapes_case_names() {
+        let record = Record {
+            case: "a \"quoted\" name".to_string(),
+            strategy: "greedy",
+            moves: 3,
+            proven_optimal: false,
+            stopped: Some(StopReason::TimeLimit),
+            nodes_expanded: 0,
+            peak_bytes: 0,
+            elapsed: Duration::from_millis(5),
+        };
+        let json = to_json(&[record]);
+        assert!(json.contains("\"case\": \"a \\\"quoted\\\" name\""));
+        assert!(json.contains("\"stopped\": \"time limit reached\""));
+        assert!(json.starts_with("[\n  {") && json.ends_with("}\n]\n"));
+    }
+}
@@ -0,0 +1,150 @@
+//! Admissible lower bounds on the moves a flood state still needs, used to
+//! prune searches and to certify how far a solution can be from optimal.
+
+use std::fmt;
+
+use crate::region::{FloodState, RegionGraph};
+use crate::Grid;
+
+/// Lower bounds on the number of moves still needed to flood a state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    /// Number of distinct colours present outside the flooded region. Every one
+    /// of them has to be played at least once.
+    pub colors_remaining: usize,
+    /// Eccentricity of the flooded region in the component graph. A move only
+    /// absorbs components adjacent to the region, so the farthest component is
+    /// at least this many moves away.
+    pub eccentricity: usize,
+    /// Relaxation keeping only when each colour can be played at the
+    /// earliest: components `j` hops away cannot be absorbed before move `j`,
+    /// so every colour found `j` or more hops away needs a move of its own at
+    /// move `j` or later. The largest `j - 1 + colours` over `j` dominates
+    /// both bounds above.
+    pub layered: usize,
+}
+
+impl Bounds {
+    pub fn compute(graph: &RegionGraph, state: &FloodState) -> Self {
+        // Farthest distance at which each colour appears
+        let mut reach = vec![0; graph.colors];
+        for (c, d) in graph.distances(state).into_iter().enumerate() {
+            if d != usize::MAX {
+                let color = graph.component_colors[c] as usize;
+                reach[color] = reach[color].max(d);
+            }
+        }
+        let eccentricity = reach.iter().copied().max().unwrap_or(0);
+
+        // Colours appearing at each distance or farther
+        let mut beyond = vec![0; eccentricity + 2];
+        for &d in reach.iter().filter(|&&d| d > 0) {
+            beyond[d] += 1;
+        }
+        for d in (1..=eccentricity).rev() {
+            beyond[d] += beyond[d + 1];
+        }
+        let layered = (1..=eccentricity).map(|j| j - 1 + beyond[j]).max().unwrap_or(0);
+
+        Bounds { colors_remaining: graph.colors_remaining(state), eccentricity, layered }
+    }
+
+    /// Bounds on the whole grid, before any move.
+    pub fn of_grid(grid: &Grid) -> Self {
+        let graph = RegionGraph::new(grid);
+        Bounds::compute(&graph, &graph.initial_state())
+    }
+
+    /// The strongest of the admissible bounds.
+    pub fn value(&self) -> usize {
+        self.colors_remaining.max(self.eccentricity).max(self.layered)
+    }
+}
+
+impl fmt::Display for Bounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Colours remaining: {}", self.colors_remaining)?;
+        writeln!(f, "Eccentricity: {}", self.eccentricity)?;
+        writeln!(f, "Layered colours: {}", self.layered)?;
+        writeln!(f, "Lower bound: {}", self.value())
+    }
+}
+
+/// How far a solution of `moves` moves can be from optimal, given a lower
+/// bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    pub bound: usize,
+    pub moves: usize,
+}
+
+impl Gap {
+    /// Moves an optimal solution could save at most.
+    pub fn moves_over(&self) -> usize {
+        self.moves.saturating_sub(self.bound)
+    }
+
+    pub fn is_optimal(&self) -> bool {
+        self.moves <= self.bound
+    }
+}
+
+impl fmt::Display for Gap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_optimal() {
+            write!(f, "optimal: {} moves, and no solution has fewer", self.moves)
+        } else {
+            write!(
+                f,
+                "{} moves, at most {} over optimal ({:.1}%)",
+                self.moves,
+                self.moves_over(),
+                100.0 * self.moves_over() as f64 / self.bound.max(1) as f64
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::astar::solve_astar;
+    use crate::budget::Budget;
+    use crate::generate::{generate, Pattern};
+    use crate::table::DEFAULT_TABLE_BYTES;
+
+    #[test]
+    fn test_bounds_on_sample() {
+        let grid = Grid::from_csv("1,2,0,0\n0,1,1,0\n2,2,0,1\n0,0,0,1").unwrap();
+        let bounds = Bounds::of_grid(&grid);
+        assert_eq!(bounds.colors_remaining, 3);
+        assert_eq!(bounds.eccentricity, 3);
+        assert_eq!(bounds.layered, 4);
+        assert_eq!(bounds.value(), 4);
+    }
+
+    #[test]
+    fn test_bounds_are_admissible_and_consistent() {
+        for seed in 0..8 {
+            let grid = generate(6, 6, 4, Pattern::Uniform, seed);
+            let optimal = solve_astar(&grid, DEFAULT_TABLE_BYTES, &Budget::unlimited(), &mut |_| {}).moves;
+            let graph = RegionGraph::new(&grid);
+            let mut state = graph.initial_state();
+            let mut bound = Bounds::compute(&graph, &state).value();
+            assert!(bound <= optimal.len(), "seed {}: bound {} above {}", seed, bound, optimal.len());
+            for (played, &color) in optimal.iter().enumerate() {
+                state = graph.play(&state, color);
+                let next = Bounds::compute(&graph, &state).value();
+                assert!(next + 1 >= bound && next < optimal.len() - played);
+                bound = next;
+            }
+            assert_eq!(bound, 0);
+        }
+    }
+
+    #[test]
+    fn test_gap() {
+        assert_eq!(Gap { bound: 10, moves: 12 }.to_string(), "12 moves, at most 2 over optimal (20.0%)");
+        assert!(Gap { bound: 10, moves: 10 }.is_optimal());
+    }
+}
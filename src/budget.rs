@@ -0,0 +1,166 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Why a search stopped before it could prove its result optimal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Interrupted,
+    TimeLimit,
+    NodeLimit,
+    MemoryLimit,
+}
+
+impl StopReason {
+    fn code(self) -> u8 {
+        match self {
+            StopReason::Interrupted => 1,
+            StopReason::TimeLimit => 2,
+            StopReason::NodeLimit => 3,
+            StopReason::MemoryLimit => 4,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(StopReason::Interrupted),
+            2 => Some(StopReason::TimeLimit),
+            3 => Some(StopReason::NodeLimit),
+            4 => Some(StopReason::MemoryLimit),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            StopReason::Interrupted => "interrupted",
+            StopReason::TimeLimit => "time limit reached",
+            StopReason::NodeLimit => "node limit reached",
+            StopReason::MemoryLimit => "memory limit reached",
+        })
+    }
+}
+
+/// Limits a solve may be given. All of them are optional.
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    pub time: Option<Duration>,
+    pub nodes: Option<usize>,
+    /// Approximate cap on search memory, in bytes.
+    pub memory: Option<usize>,
+    /// Raised from outside (e.g. on Ctrl-C) to stop every running search.
+    pub stop: Option<Arc<AtomicBool>>,
+}
+
+/// Live accounting of `Limits` during one solve, shared between threads.
+///
+/// Once any limit trips the budget stays exhausted and remembers the first
+/// reason, so solvers can unwind and return their incumbent.
+#[derive(Debug)]
+pub struct Budget {
+    deadline: Option<Instant>,
+    node_limit: Option<usize>,
+    memory_limit: Option<usize>,
+    stop: Option<Arc<AtomicBool>>,
+    nodes: AtomicUsize,
+    reason: AtomicU8,
+}
+
+impl Budget {
+    pub fn new(limits: &Limits) -> Self {
+        Budget {
+            deadline: limits.time.map(|t| Instant::now() + t),
+            node_limit: limits.nodes,
+            memory_limit: limits.memory,
+            stop: limits.stop.clone(),
+            nodes: AtomicUsize::new(0),
+            reason: AtomicU8::new(0),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Budget::new(&Limits::default())
+    }
+
+    fn trip(&self, reason: StopReason) -> bool {
+        let _ = self.reason.compare_exchange(0, reason.code(), Ordering::Relaxed, Ordering::Relaxed);
+        true
+    }
+
+    /// Checks the interrupt flag and the deadline.
+    pub fn exhausted(&self) -> bool {
+        if self.reason.load(Ordering::Relaxed) != 0 {
+            return true;
+        }
+        if self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+            return self.trip(StopReason::Interrupted);
+        }
+        if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return self.trip(StopReason::TimeLimit);
+        }
+        false
+    }
+
+    /// Counts one node expansion and reports whether the search must stop.
+    pub fn expand(&self) -> bool {
+        let nodes = self.nodes.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.node_limit.is_some_and(|limit| nodes > limit) {
+            return self.trip(StopReason::NodeLimit);
+        }
+        self.exhausted()
+    }
+
+    /// Reports whether a search currently using `bytes` must stop.
+    pub fn exceeds_memory(&self, bytes: usize) -> bool {
+        self.memory_limit.is_some_and(|limit| bytes > limit) && self.trip(StopReason::MemoryLimit)
+    }
+
+    /// Memory a transposition table may use: the configured size, capped by
+    /// the memory limit.
+    pub fn table_bytes(&self, configured: usize) -> usize {
+        self.memory_limit.map_or(configured, |limit| configured.min(limit / 2))
+    }
+
+    pub fn nodes(&self) -> usize {
+        self.nodes.load(Ordering::Relaxed)
+    }
+
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        StopReason::from_code(self.reason.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_limit() {
+        let budget = Budget::new(&Limits { nodes: Some(2), ..Limits::default() });
+        assert!(!budget.expand());
+        assert!(!budget.expand());
+        assert!(budget.expand());
+        assert_eq!(budget.stop_reason(), Some(StopReason::NodeLimit));
+    }
+
+    #[test]
+    fn test_first_reason_is_kept() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let budget = Budget::new(&Limits { memory: Some(10), stop: Some(stop.clone()), ..Limits::default() });
+        assert!(!budget.exhausted());
+        assert!(budget.exceeds_memory(11));
+        stop.store(true, Ordering::Relaxed);
+        assert!(budget.exhausted());
+        assert_eq!(budget.stop_reason(), Some(StopReason::MemoryLimit));
+    }
+
+    #[test]
+    fn test_zero_time_limit_is_immediately_exhausted() {
+        let budget = Budget::new(&Limits { time: Some(Duration::ZERO), ..Limits::default() });
+        assert!(budget.exhausted());
+        assert_eq!(budget.stop_reason(), Some(StopReason::TimeLimit));
+    }
+}
@@ -0,0 +1,159 @@
+use crate::astar::{state_bytes, SearchResult};
+use crate::bound::Bounds;
+use crate::budget::Budget;
+use crate::greedy::{self, GreedyScore};
+use crate::region::{FloodState, RegionGraph};
+use crate::table::TranspositionTable;
+use crate::Grid;
+
+/// Stack-based depth-first search over flood states.
+///
+/// States are only expanded the first time they are seen, so the search
+/// terminates quickly on small grids but its result is not guaranteed to be
+/// optimal. States whose lower bound cannot beat the best solution so far
+/// are pruned, starting from the greedy solution so that the bounds prune
+/// from the first node. `on_improve` is called with every new best solution.
+pub fn solve_dfs(
+    grid: &Grid,
+    table_bytes: usize,
+    budget: &Budget,
+    on_improve: &mut dyn FnMut(&[u8]),
+) -> SearchResult {
+    struct SearchState {
+        moves: Vec<u8>,
+        state: FloodState, // Flooded components and their colour
+    }
+
+    let graph = RegionGraph::new(grid);
+    let root = graph.initial_state();
+    let mut stack: Vec<SearchState> = Vec::new();
+    let mut visited = TranspositionTable::new(table_bytes, graph.component_count());
+    let mut nodes_expanded = 0;
+    let entry_bytes = state_bytes(&graph) + std::mem::size_of::<SearchState>() + grid.colors;
+
+    if graph.is_complete(&root) {
+        return SearchResult { moves: Vec::new(), proven_optimal: true, nodes_expanded, table: visited.stats() };
+    }
+
+    let mut best_solution = greedy::complete_greedily(&graph, &root, GreedyScore::Cells, budget);
+    let mut min_length = best_solution.len();
+    on_improve(&best_solution);
+
+    // Initialize the stack with the first moves
+    for color in 0..grid.colors {
+        let color = color as u8;
+        if color != root.color || root.mixed {
+            let next = graph.play(&root, color);
+
+            if visited.probe(&next).is_none() {
+                visited.store(next.clone(), 1);
+                stack.push(SearchState {
+                    moves: vec![color],
+                    state: next,
+                });
+            }
+        }
+    }
+
+    // Perform the search
+    while let Some(current) = stack.pop() {
+        if current.moves.len() + Bounds::compute(&graph, &current.state).value() >= min_length {
+            continue;
+        }
+
+        // Check if the grid is complete
+        if graph.is_complete(&current.state) {
+            if current.moves.len() < min_length {
+                best_solution = current.moves.clone();
+                min_length = current.moves.len();
+                on_improve(&best_solution);
+            }
+            continue;
+        }
+
+        if budget.expand() || budget.exceeds_memory(stack.len() * entry_bytes + visited.stats().bytes) {
+            break;
+        }
+        nodes_expanded += 1;
+
+        // Add next possible moves
+        for color in 0..grid.colors {
+            let color = color as u8;
+
+            // Skip moves that repeat the current color or backtrack
+            if color == current.state.color || (current.moves.last() == Some(&color)) {
+                continue;
+            }
+
+            let next = graph.play(&current.state, color);
+
+            // Only consider this move if it results in a new flood state
+            if visited.probe(&next).is_none() {
+                visited.store(next.clone(), current.moves.len() + 1);
+                let mut next_moves = current.moves.clone();
+                next_moves.push(color);
+
+                stack.push(SearchState {
+                    moves: next_moves,
+                    state: next,
+                });
+            }
+        }
+    }
+
+    SearchResult { moves: best_solution, proven_optimal: false, nodes_expanded, table: visited.stats() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::Limits;
+    use crate::table::DEFAULT_TABLE_BYTES;
+
+    fn solve(grid: &Grid) -> Vec<u8> {
+        solve_dfs(grid, DEFAULT_TABLE_BYTES, &Budget::unlimited(), &mut |_| {}).moves
+    }
+
+    #[test]
+    fn test_simplest_input() {
+        let input = "0,1\n1,1";
+        let grid = Grid::from_csv(input).unwrap();
+        let solution = solve(&grid);
+        assert_eq!(solution, vec![1]);
+    }
+
+    #[test]
+    fn test_medium_input() {
+        let input = "2,1,3,0,4\n1,2,2,3,1\n0,3,1,2,4\n4,1,0,3,2\n3,2,4,1,0";
+        let grid = Grid::from_csv(input).unwrap();
+        let solution = solve(&grid);
+        let mut test_grid = grid.clone();
+        for &color in &solution {
+            test_grid.flood_fill(color);
+        }
+        assert!(test_grid.is_complete());
+        assert!(solution.len() <= 16);
+    }
+
+    #[test]
+    fn test_sample_input() {
+        let input = "1,2,0,0\n0,1,1,0\n2,2,0,1\n0,0,0,1";
+        let grid = Grid::from_csv(input).unwrap();
+        let solution = solve(&grid);
+        assert_eq!(solution, vec![0, 2, 0, 1]);
+
+        let mut test_grid = grid.clone();
+        assert!(test_grid.apply_solution(&solution));
+    }
+
+    #[test]
+    fn test_node_limit_falls_back_to_greedy() {
+        let grid = Grid::from_csv("2,1,3,0,4\n1,2,2,3,1\n0,3,1,2,4\n4,1,0,3,2\n3,2,4,1,0").unwrap();
+        let budget = Budget::new(&Limits { nodes: Some(1), ..Limits::default() });
+        let result = solve_dfs(&grid, DEFAULT_TABLE_BYTES, &budget, &mut |_| {});
+        assert!(grid.is_solution(&result.moves));
+        assert!(!result.proven_optimal);
+        let greedy = greedy::solve_greedy(&grid, GreedyScore::Cells, &Budget::unlimited());
+        assert!(result.moves.len() <= greedy.len());
+    }
+}
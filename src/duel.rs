@@ -0,0 +1,424 @@
+//! Two-player Flood-It. Each player owns a region grown from their own
+//! corner; they alternately recolour it, absorbing the unowned cells of the
+//! new colour that border it. A player may pick neither their current colour
+//! nor the opponent's. The game ends when no unowned cell borders either
+//! region, and whoever owns more cells wins.
+
+use std::fmt;
+
+use crate::topology::Topology;
+use crate::Grid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Player {
+    First,
+    Second,
+}
+
+impl Player {
+    pub fn index(self) -> usize {
+        match self {
+            Player::First => 0,
+            Player::Second => 1,
+        }
+    }
+
+    pub fn other(self) -> Player {
+        match self {
+            Player::First => Player::Second,
+            Player::Second => Player::First,
+        }
+    }
+}
+
+impl fmt::Display for Player {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "player {}", self.index() + 1)
+    }
+}
+
+/// Position of a two-player game.
+#[derive(Debug, Clone)]
+pub struct Duel {
+    width: usize,
+    height: usize,
+    topology: Topology,
+    colors: usize,
+    /// Current colour of every cell, row-major.
+    cells: Vec<u8>,
+    walls: Vec<bool>,
+    owners: Vec<Option<Player>>,
+    owned: [usize; 2],
+    player_colors: [u8; 2],
+    open_cells: usize,
+    to_move: Player,
+    plies: usize,
+    over: bool,
+}
+
+impl Duel {
+    /// Starts a game on `grid`, the first player to move. The players start
+    /// from the grid's two origins if it has exactly two, otherwise from the
+    /// top-left and bottom-right cells.
+    ///
+    /// A game needs at least three colours, so that a move is always legal,
+    /// and starting cells whose regions are distinct.
+    pub fn new(grid: &Grid) -> Result<Self, String> {
+        let (width, height) = (grid.width(), grid.height());
+        let starts = match grid.origins() {
+            &[first, second] => [first, second],
+            _ => [(0, 0), (width - 1, height - 1)],
+        };
+        if grid.colors() < 3 {
+            return Err(format!("a duel needs at least 3 colours, the grid has {}", grid.colors()));
+        }
+        if let Some(&(x, y)) = starts.iter().find(|&&(x, y)| grid.is_wall(x, y)) {
+            return Err(format!("starting cell ({}, {}) is a wall", x, y));
+        }
+
+        let mut duel = Duel {
+            width,
+            height,
+            topology: grid.topology(),
+            colors: grid.colors(),
+            cells: (0..width * height).map(|i| grid.get(i % width, i / width)).collect(),
+            walls: (0..width * height).map(|i| grid.is_wall(i % width, i / width)).collect(),
+            owners: vec![None; width * height],
+            owned: [0; 2],
+            player_colors: [0; 2],
+            open_cells: grid.open_cells(),
+            to_move: Player::First,
+            plies: 0,
+            over: false,
+        };
+        for (player, (x, y)) in [Player::First, Player::Second].into_iter().zip(starts) {
+            let start = y * width + x;
+            if duel.owners[start].is_some() {
+                return Err("both players start in the same region".to_string());
+            }
+            duel.owners[start] = Some(player);
+            duel.owned[player.index()] = 1;
+            duel.player_colors[player.index()] = duel.cells[start];
+            duel.grow(player, vec![start]);
+        }
+        duel.over = duel.frontier_colors(Player::First).is_empty() && duel.frontier_colors(Player::Second).is_empty();
+        Ok(duel)
+    }
+
+    fn neighbours(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        let width = self.width;
+        self.topology
+            .neighbours(i % width, i / width, width, self.height)
+            .map(move |(x, y)| y * width + x)
+            .filter(move |&n| !self.walls[n])
+    }
+
+    /// Gives `player` the unowned cells of their colour connected to `stack`.
+    fn grow(&mut self, player: Player, mut stack: Vec<usize>) -> usize {
+        let color = self.player_colors[player.index()];
+        let before = self.owned[player.index()];
+        let (width, height, topology) = (self.width, self.height, self.topology);
+        while let Some(i) = stack.pop() {
+            for (x, y) in topology.neighbours(i % width, i / width, width, height) {
+                let n = y * width + x;
+                if !self.walls[n] && self.owners[n].is_none() && self.cells[n] == color {
+                    self.owners[n] = Some(player);
+                    self.owned[player.index()] += 1;
+                    stack.push(n);
+                }
+            }
+        }
+        self.owned[player.index()] - before
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn colors(&self) -> usize {
+        self.colors
+    }
+
+    pub fn to_move(&self) -> Player {
+        self.to_move
+    }
+
+    /// Moves played so far.
+    pub fn plies(&self) -> usize {
+        self.plies
+    }
+
+    pub fn color(&self, player: Player) -> u8 {
+        self.player_colors[player.index()]
+    }
+
+    /// Number of cells `player` owns.
+    pub fn owned(&self, player: Player) -> usize {
+        self.owned[player.index()]
+    }
+
+    pub fn owner(&self, x: usize, y: usize) -> Option<Player> {
+        self.owners[y * self.width + x]
+    }
+
+    pub fn open_cells(&self) -> usize {
+        self.open_cells
+    }
+
+    pub fn is_legal(&self, color: u8) -> bool {
+        (color as usize) < self.colors && !self.player_colors.contains(&color)
+    }
+
+    /// Colours the player to move may pick, in increasing order.
+    pub fn legal_moves(&self) -> Vec<u8> {
+        (0..self.colors as u8).filter(|&c| self.is_legal(c)).collect()
+    }
+
+    /// Sorted colours of the unowned cells bordering the region of `player`.
+    pub fn frontier_colors(&self, player: Player) -> Vec<u8> {
+        let mut seen = [false; 256];
+        for i in 0..self.cells.len() {
+            if self.owners[i] == Some(player) {
+                for n in self.neighbours(i) {
+                    if self.owners[n].is_none() {
+                        seen[self.cells[n] as usize] = true;
+                    }
+                }
+            }
+        }
+        (0..=u8::MAX).filter(|&c| seen[c as usize]).collect()
+    }
+
+    /// Recolours the region of the player to move with `color` and returns
+    /// how many cells it absorbed. The turn then passes to the opponent.
+    ///
+    /// # Panics
+    ///
+    /// If the game is over or `color` is not legal.
+    pub fn play(&mut self, color: u8) -> usize {
+        assert!(!self.over, "the game is over");
+        assert!(self.is_legal(color), "colour {} is not a legal move", color);
+        let player = self.to_move;
+        self.player_colors[player.index()] = color;
+        let mut region = Vec::with_capacity(self.owned[player.index()]);
+        for i in 0..self.cells.len() {
+            if self.owners[i] == Some(player) {
+                self.cells[i] = color;
+                region.push(i);
+            }
+        }
+        let absorbed = self.grow(player, region);
+        self.to_move = player.other();
+        self.plies += 1;
+        self.over = self.frontier_colors(Player::First).is_empty() && self.frontier_colors(Player::Second).is_empty();
+        absorbed
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.over
+    }
+
+    /// Player owning more cells, `None` on a tie.
+    pub fn leader(&self) -> Option<Player> {
+        match self.owned[0].cmp(&self.owned[1]) {
+            std::cmp::Ordering::Greater => Some(Player::First),
+            std::cmp::Ordering::Less => Some(Player::Second),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+}
+
+/// Rows of cells with `*` for the first player's region, `o` for the
+/// second's, `#` for walls and the colour of the others.
+impl fmt::Display for Duel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cell_width = (self.colors.max(1) - 1).to_string().len();
+        for y in 0..self.height {
+            let indent = self.topology.row_shift(y) * (cell_width + 1) / 2;
+            write!(f, "{:indent$}", "", indent = indent)?;
+            for x in 0..self.width {
+                if x > 0 {
+                    f.write_str(" ")?;
+                }
+                let i = y * self.width + x;
+                let cell = match self.owners[i] {
+                    _ if self.walls[i] => "#".to_string(),
+                    Some(Player::First) => "*".to_string(),
+                    Some(Player::Second) => "o".to_string(),
+                    None => self.cells[i].to_string(),
+                };
+                write!(f, "{:>w$}", cell, w = cell_width)?;
+            }
+            writeln!(f)?;
+        }
+        for (player, mark) in [(Player::First, '*'), (Player::Second, 'o')] {
+            writeln!(f, "{} ({}): colour {}, {} cells", player, mark, self.color(player), self.owned(player))?;
+        }
+        Ok(())
+    }
+}
+
+/// Something that picks moves: an engine or a person at the terminal.
+pub trait Agent {
+    /// Name shown in match reports.
+    fn name(&self) -> String;
+
+    /// Picks a legal colour for the player to move, or `None` to resign.
+    fn choose(&mut self, duel: &Duel) -> Option<u8>;
+}
+
+/// How a game ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    /// `None` for a draw.
+    pub winner: Option<Player>,
+    /// Cells owned by each player at the end.
+    pub owned: [usize; 2],
+    pub plies: usize,
+    /// Set when the loser resigned rather than ran out of moves.
+    pub resigned: bool,
+}
+
+/// Games stop after this many plies per open cell, so that two players
+/// refusing to grow cannot go on forever; the leader then wins.
+pub const PLIES_PER_CELL: usize = 4;
+
+/// Plays a game from `duel` to the end. `agents[0]` moves for the first
+/// player. `on_move` sees each position after a move.
+pub fn play_game(mut duel: Duel, agents: [&mut dyn Agent; 2], on_move: &mut dyn FnMut(&Duel, u8)) -> Outcome {
+    let limit = PLIES_PER_CELL * duel.open_cells();
+    let [first, second] = agents;
+    while !duel.is_over() && duel.plies() < limit {
+        let player = duel.to_move();
+        let agent: &mut dyn Agent = if player == Player::First { &mut *first } else { &mut *second };
+        match agent.choose(&duel) {
+            Some(color) => {
+                duel.play(color);
+                on_move(&duel, color);
+            }
+            None => {
+                return Outcome {
+                    winner: Some(player.other()),
+                    owned: duel.owned,
+                    plies: duel.plies(),
+                    resigned: true,
+                }
+            }
+        }
+    }
+    Outcome { winner: duel.leader(), owned: duel.owned, plies: duel.plies(), resigned: false }
+}
+
+/// Results of a match, from the point of view of the two agents rather than
+/// the two seats.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MatchReport {
+    pub names: [String; 2],
+    pub games: usize,
+    pub wins: [usize; 2],
+    pub draws: usize,
+}
+
+impl MatchReport {
+    /// Share of the games agent `index` won, draws counting half.
+    pub fn score(&self, index: usize) -> f64 {
+        if self.games == 0 {
+            return 0.0;
+        }
+        (self.wins[index] as f64 + self.draws as f64 / 2.0) / self.games as f64
+    }
+}
+
+impl fmt::Display for MatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} games, {} drawn", self.games, self.draws)?;
+        for index in 0..2 {
+            let rate = if self.games == 0 { 0.0 } else { 100.0 * self.wins[index] as f64 / self.games as f64 };
+            writeln!(
+                f,
+                "{:<16} {:>4} wins  {:>5.1}% win rate  {:>5.1}% score",
+                self.names[index],
+                self.wins[index],
+                rate,
+                100.0 * self.score(index)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Plays two games per grid, the agents swapping seats between them, and
+/// reports each outcome to `on_game` with the index of the agent that moved
+/// first.
+pub fn run_match(
+    grids: &[Grid],
+    agents: [&mut dyn Agent; 2],
+    on_game: &mut dyn FnMut(usize, usize, &Outcome),
+) -> Result<MatchReport, String> {
+    let [a, b] = agents;
+    let mut report = MatchReport { names: [a.name(), b.name()], ..MatchReport::default() };
+    for (index, grid) in grids.iter().enumerate() {
+        for first in 0..2 {
+            let duel = Duel::new(grid)?;
+            let seats: [&mut dyn Agent; 2] = if first == 0 { [&mut *a, &mut *b] } else { [&mut *b, &mut *a] };
+            let outcome = play_game(duel, seats, &mut |_, _| {});
+            match outcome.winner {
+                None => report.draws += 1,
+                Some(player) => report.wins[if player == Player::First { first } else { 1 - first }] += 1,
+            }
+            report.games += 1;
+            on_game(index, first, &outcome);
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "0,1,2\n1,1,2\n0,2,1";
+
+    #[test]
+    fn test_rules() {
+        let mut duel = Duel::new(&Grid::from_csv(SAMPLE).unwrap()).unwrap();
+        assert_eq!((duel.owned(Player::First), duel.owned(Player::Second)), (1, 1));
+        assert_eq!((duel.color(Player::First), duel.color(Player::Second)), (0, 1));
+        assert_eq!(duel.legal_moves(), vec![2]);
+        assert!(!duel.is_legal(1));
+
+        assert_eq!(duel.play(2), 0);
+        assert_eq!(duel.to_move(), Player::Second);
+        assert_eq!(duel.legal_moves(), vec![0]);
+        assert_eq!(duel.play(0), 0);
+        assert_eq!(duel.frontier_colors(Player::First), vec![1]);
+        assert_eq!(duel.play(1), 3);
+        assert_eq!(duel.owned(Player::First), 4);
+        assert!(!duel.is_over());
+    }
+
+    #[test]
+    fn test_game_ends_when_every_cell_is_owned() {
+        let mut duel = Duel::new(&Grid::from_csv("0,1,2\n1,2,0\n2,0,1").unwrap()).unwrap();
+        while !duel.is_over() {
+            let colors = duel.frontier_colors(duel.to_move());
+            let color = colors.into_iter().find(|&c| duel.is_legal(c)).unwrap_or(duel.legal_moves()[0]);
+            duel.play(color);
+        }
+        assert_eq!(duel.owned(Player::First) + duel.owned(Player::Second), 9);
+        assert!(duel.leader().is_some());
+    }
+
+    #[test]
+    fn test_invalid_setups() {
+        assert!(Duel::new(&Grid::from_csv("0,1\n1,0").unwrap()).is_err());
+        assert!(Duel::new(&Grid::from_csv("0,1,2\n0,0,0").unwrap()).is_err());
+        let grid = Grid::from_csv("start: 0,0; 1,0\n0,1,2\n2,0,1").unwrap();
+        let duel = Duel::new(&grid).unwrap();
+        assert_eq!((duel.owner(1, 0), duel.owner(2, 1)), (Some(Player::Second), None));
+    }
+}
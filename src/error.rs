@@ -0,0 +1,72 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::topology::Topology;
+
+/// Why a grid CSV was rejected. Lines and columns are 1-based and refer to
+/// the original input, columns counting comma-separated cells.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input has no rows.
+    Empty,
+    /// A row does not have as many cells as the first one.
+    RaggedRow { line: usize, expected: usize, actual: usize },
+    /// A cell is neither a non-negative integer nor a wall marker.
+    InvalidColor { line: usize, column: usize, token: String },
+    /// A cell is an integer above 255.
+    ColorOutOfRange { line: usize, column: usize, token: String },
+    /// The grid is not square although squareness was required.
+    NotSquare { width: usize, height: usize },
+    /// A directive line before the rows has an unknown key.
+    UnknownDirective { line: usize, key: String },
+    /// The `topology` directive names no known topology.
+    UnknownTopology { line: usize, name: String },
+    /// An entry of the `start` directive is not an `x,y` pair.
+    InvalidStart { line: usize, value: String },
+    /// The `start` directive names a cell outside the grid.
+    StartOutOfBounds { line: usize, x: usize, y: usize },
+    /// The flood starts at this cell, which must not be a wall.
+    WallAtOrigin { line: usize, column: usize },
+    /// Walls cut this cell off from every origin, so it can never be flooded.
+    Unreachable { line: usize, column: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "empty input"),
+            ParseError::RaggedRow { line, expected, actual } => {
+                write!(f, "line {}: expected {} cells like the first row, found {}", line, expected, actual)
+            }
+            ParseError::InvalidColor { line, column, token } => {
+                write!(f, "line {}, column {}: invalid colour {:?}, expected a number from 0 to 255", line, column, token)
+            }
+            ParseError::ColorOutOfRange { line, column, token } => {
+                write!(f, "line {}, column {}: colour {} is above 255", line, column, token)
+            }
+            ParseError::NotSquare { width, height } => {
+                write!(f, "grid is {}x{} but must be square", width, height)
+            }
+            ParseError::UnknownDirective { line, key } => {
+                write!(f, "line {}: unknown directive {:?}, expected \"start: <x>,<y>\" or \"topology: <name>\"", line, key)
+            }
+            ParseError::UnknownTopology { line, name } => {
+                write!(f, "line {}: unknown topology {:?}, expected one of {}", line, name, Topology::NAMES.join(", "))
+            }
+            ParseError::InvalidStart { line, value } => {
+                write!(f, "line {}: invalid start {:?}, expected <x>,<y> pairs separated by \";\"", line, value)
+            }
+            ParseError::StartOutOfBounds { line, x, y } => {
+                write!(f, "line {}: start ({}, {}) is outside the grid", line, x, y)
+            }
+            ParseError::WallAtOrigin { line, column } => {
+                write!(f, "line {}, column {}: the flood starts from this cell, which is a wall", line, column)
+            }
+            ParseError::Unreachable { line, column } => {
+                write!(f, "line {}, column {}: cell is walled off from the starting cells and can never be flooded", line, column)
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
@@ -0,0 +1,398 @@
+//! Pictures of grids: PNG stills, and animations of a solution being played
+//! as GIF or APNG, one frame per move.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+use crate::flood::Flood;
+use crate::render;
+use crate::Grid;
+
+/// How cells are drawn.
+#[derive(Debug, Clone)]
+pub struct Style {
+    /// Side of a cell, in pixels.
+    pub cell_size: usize,
+    /// Colours of the first colour indices; the others use `render::rgb`.
+    pub palette: Vec<[u8; 3]>,
+    /// Outline of the flooded region, if any.
+    pub outline: Option<[u8; 3]>,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style { cell_size: 8, palette: Vec::new(), outline: None }
+    }
+}
+
+impl Style {
+    pub fn rgb(&self, color: u8) -> [u8; 3] {
+        self.palette.get(color as usize).copied().unwrap_or_else(|| render::rgb(color))
+    }
+}
+
+/// Parses a palette written as comma-separated `#rrggbb` colours, the `#`
+/// being optional.
+pub fn parse_palette(text: &str) -> Result<Vec<[u8; 3]>, String> {
+    text.split(',')
+        .map(|entry| {
+            let hex = entry.trim().trim_start_matches('#');
+            let channel = |i: usize| hex.get(i..i + 2).and_then(|h| u8::from_str_radix(h, 16).ok());
+            match (hex.len(), channel(0), channel(2), channel(4)) {
+                (6, Some(r), Some(g), Some(b)) => Ok([r, g, b]),
+                _ => Err(format!("invalid colour {:?}, expected #rrggbb", entry.trim())),
+            }
+        })
+        .collect()
+}
+
+/// A colour written as `#rrggbb`, as `parse_palette` reads it.
+pub fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// An RGB picture, row-major, three bytes per pixel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        let i = 3 * (y * self.width + x);
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]]
+    }
+}
+
+/// Draws `grid`, outlining the cells for which `flooded` holds when the style
+/// asks for it. Hexagonal rows are shifted like in the terminal rendering.
+pub fn draw(grid: &Grid, flooded: &dyn Fn(usize, usize) -> bool, style: &Style) -> Image {
+    let size = style.cell_size.max(1);
+    let shift = |y: usize| grid.topology().row_shift(y) * size / 2;
+    let max_shift = (0..grid.height()).map(shift).max().unwrap_or(0);
+    let (width, height) = (grid.width() * size + max_shift, grid.height() * size);
+
+    // Cell under a pixel, if any
+    let cell_at = |px: usize, py: usize| {
+        let y = py / size;
+        (py < height && px >= shift(y) && px < shift(y) + grid.width() * size).then(|| ((px - shift(y)) / size, y))
+    };
+
+    let thickness = (size / 6).max(1);
+    let is_flooded = |p: Option<(usize, usize)>| p.is_some_and(|(x, y)| !grid.is_wall(x, y) && flooded(x, y));
+    let at = |qx: Option<usize>, qy: Option<usize>| match (qx, qy) {
+        (Some(qx), Some(qy)) => cell_at(qx, qy),
+        _ => None,
+    };
+    // Pixels closer to an unflooded cell than the outline thickness
+    let edge = |px: usize, py: usize| {
+        (1..=thickness).any(|d| {
+            [at(px.checked_sub(d), Some(py)), at(Some(px + d), Some(py)), at(Some(px), py.checked_sub(d)), at(Some(px), Some(py + d))]
+                .into_iter()
+                .any(|n| !is_flooded(n))
+        })
+    };
+
+    // White background, then each cell painted as a block
+    let mut pixels = vec![255; 3 * width * height];
+    let mut paint = |px: usize, py: usize, len: usize, rgb: [u8; 3]| {
+        let i = 3 * (py * width + px);
+        pixels[i..i + 3 * len].chunks_exact_mut(3).for_each(|pixel| pixel.copy_from_slice(&rgb));
+    };
+    for y in 0..grid.height() {
+        for x in 0..grid.width() {
+            let (left, top) = (shift(y) + x * size, y * size);
+            let rgb = if grid.is_wall(x, y) { render::WALL } else { style.rgb(grid.get(x, y)) };
+            for py in top..top + size {
+                paint(left, py, size, rgb);
+            }
+            match style.outline {
+                Some(outline) if is_flooded(Some((x, y))) => {
+                    // The outline can only reach the neighbours in the row, and
+                    // the cells above and below both ends of the block
+                    let (right, bottom) = (left + size - 1, top + size);
+                    let around = [
+                        at(left.checked_sub(1), Some(top)),
+                        at(Some(right + 1), Some(top)),
+                        at(Some(left), top.checked_sub(1)),
+                        at(Some(right), top.checked_sub(1)),
+                        at(Some(left), Some(bottom)),
+                        at(Some(right), Some(bottom)),
+                    ];
+                    if around.into_iter().all(&is_flooded) {
+                        continue;
+                    }
+                    // Only pixels within `thickness` of the block's sides can be on the edge
+                    let border = |p: usize, start: usize| p - start < thickness || p - start + thickness >= size;
+                    for py in top..top + size {
+                        for px in left..left + size {
+                            if (border(px, left) || border(py, top)) && edge(px, py) {
+                                paint(px, py, 1, outline);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Image { width, height, pixels }
+}
+
+/// One frame for the initial grid, then one after each move, drawn as they
+/// are consumed so that only one is in memory at a time.
+pub fn frames<'a>(grid: &Grid, moves: &'a [u8], style: &'a Style) -> impl ExactSizeIterator<Item = Image> + 'a {
+    let mut flood = Flood::new(grid);
+    (0..moves.len() + 1).map(move |i| {
+        if i > 0 {
+            flood.play(moves[i - 1]);
+        }
+        draw(&flood.to_grid(), &|x, y| flood.is_flooded(x, y), style)
+    })
+}
+
+/// Every colour `draw` can use on `grid`: its colours, walls, the
+/// background and the outline.
+pub fn palette(grid: &Grid, style: &Style) -> Vec<[u8; 3]> {
+    let mut palette: Vec<[u8; 3]> = (0..grid.colors()).map(|c| style.rgb(c as u8)).collect();
+    palette.extend([render::WALL, [255, 255, 255]]);
+    palette.extend(style.outline);
+    let mut seen = HashSet::new();
+    palette.retain(|rgb| seen.insert(*rgb));
+    palette
+}
+
+fn png_encoder<W: Write>(writer: W, image: &Image) -> png::Encoder<'static, W> {
+    let mut encoder = png::Encoder::new(writer, image.width as u32, image.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+}
+
+pub fn write_png<W: Write>(writer: W, image: &Image) -> io::Result<()> {
+    let mut writer = png_encoder(writer, image).write_header()?;
+    writer.write_image_data(&image.pixels)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Writes an animated PNG that loops forever, each frame shown for `delay_ms`.
+/// Viewers without APNG support show the first frame.
+pub fn write_apng<W: Write>(writer: W, frames: impl ExactSizeIterator<Item = Image>, delay_ms: u16) -> io::Result<()> {
+    let count = frames.len();
+    let mut frames = frames.peekable();
+    let first = frames.peek().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no frame to write"))?;
+    let mut encoder = png_encoder(writer, first);
+    encoder.set_animated(count as u32, 0)?;
+    encoder.set_frame_delay(delay_ms, 1000)?;
+    let mut writer = encoder.write_header()?;
+    for frame in frames {
+        writer.write_image_data(&frame.pixels)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Smallest rectangle holding every pixel that differs between two frames of
+/// the same size, as (left, top, width, height); a single pixel if none does.
+fn changed_area(before: &Image, after: &Image) -> (usize, usize, usize, usize) {
+    let stride = 3 * after.width;
+    let row = |y: usize| y * stride..(y + 1) * stride;
+    let rows: Vec<usize> = (0..after.height).filter(|&y| before.pixels[row(y)] != after.pixels[row(y)]).collect();
+    let (Some(&top), Some(&bottom)) = (rows.first(), rows.last()) else {
+        return (0, 0, 1, 1);
+    };
+    let differs = |y: usize, x: usize| before.pixel(x, y) != after.pixel(x, y);
+    let left = rows.iter().filter_map(|&y| (0..after.width).find(|&x| differs(y, x))).min().unwrap_or(0);
+    let right = rows.iter().filter_map(|&y| (0..after.width).rev().find(|&x| differs(y, x))).max().unwrap_or(0);
+    (left, top, right + 1 - left, bottom + 1 - top)
+}
+
+/// Palette indices of `pixels`, or `None` if one is not in the palette.
+fn to_indices<'a>(pixels: impl Iterator<Item = &'a [u8]>, indices: &HashMap<[u8; 3], u8>) -> Option<Vec<u8>> {
+    let mut buffer = Vec::new();
+    // Cells are drawn as blocks, so most pixels repeat the previous one
+    let mut last = None;
+    for rgb in pixels {
+        let rgb = [rgb[0], rgb[1], rgb[2]];
+        let index = match last {
+            Some((seen, index)) if seen == rgb => index,
+            _ => {
+                let index = *indices.get(&rgb)?;
+                last = Some((rgb, index));
+                index
+            }
+        };
+        buffer.push(index);
+    }
+    Some(buffer)
+}
+
+/// Writes an animated GIF that loops forever, each frame shown for `delay_ms`
+/// rounded to hundredths of a second. Frames are indexed into `palette`,
+/// e.g. from `palette`, when it has at most 256 colours and holds all of
+/// their pixels, and quantised otherwise.
+/// After the first frame, only the rectangle that changed is encoded, over
+/// the previous frame.
+pub fn write_gif<W: Write>(writer: W, palette: &[[u8; 3]], frames: impl Iterator<Item = Image>, delay_ms: u16) -> io::Result<()> {
+    let mut frames = frames.peekable();
+    let first = frames.peek().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no frame to write"))?;
+    let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "GIF frames are limited to 65535 pixels a side");
+    let (width, height) = (u16::try_from(first.width).map_err(|_| too_large())?, u16::try_from(first.height).map_err(|_| too_large())?);
+
+    let indices: HashMap<[u8; 3], u8> = palette.iter().take(256).enumerate().map(|(i, &rgb)| (rgb, i as u8)).collect();
+    let global: Vec<u8> = if palette.len() <= 256 { palette.concat() } else { Vec::new() };
+    let mut encoder = gif::Encoder::new(writer, width, height, &global).map_err(io::Error::other)?;
+    encoder.set_repeat(gif::Repeat::Infinite).map_err(io::Error::other)?;
+    let mut previous: Option<Image> = None;
+    for image in frames {
+        let (left, top, w, h) = previous.as_ref().map_or((0, 0, image.width, image.height), |before| changed_area(before, &image));
+        let area = || (top..top + h).flat_map(|y| image.pixels[3 * (y * image.width + left)..3 * (y * image.width + left + w)].chunks_exact(3));
+        let indexed = if global.is_empty() { None } else { to_indices(area(), &indices) };
+        let mut frame = match indexed {
+            Some(buffer) => gif::Frame { buffer: buffer.into(), ..gif::Frame::default() },
+            None => gif::Frame::from_rgb_speed(w as u16, h as u16, &area().flatten().copied().collect::<Vec<u8>>(), 10),
+        };
+        (frame.left, frame.top, frame.width, frame.height) = (left as u16, top as u16, w as u16, h as u16);
+        frame.dispose = gif::DisposalMethod::Keep;
+        frame.delay = delay_ms.div_ceil(10);
+        encoder.write_frame(&frame).map_err(io::Error::other)?;
+        previous = Some(image);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "1,2,0,0\n0,1,1,0\n2,2,0,1\n0,0,0,1";
+
+    #[test]
+    fn test_draw() {
+        let grid = Grid::from_csv("0,1\n#,2").unwrap();
+        let style = Style { cell_size: 4, palette: vec![[1, 2, 3]], outline: None };
+        let image = draw(&grid, &|_, _| false, &style);
+        assert_eq!((image.width, image.height), (8, 8));
+        assert_eq!(image.pixel(1, 1), [1, 2, 3]);
+        assert_eq!(image.pixel(5, 2), render::rgb(1));
+        assert_eq!(image.pixel(0, 7), render::WALL);
+
+        let hex = Grid::from_csv("topology: hex\n0,1\n1,0").unwrap();
+        let image = draw(&hex, &|_, _| false, &style);
+        assert_eq!((image.width, image.height), (10, 8));
+        assert_eq!(image.pixel(0, 5), [255, 255, 255]);
+        assert_eq!(image.pixel(2, 5), render::rgb(1));
+    }
+
+    #[test]
+    fn test_outline_follows_the_flooded_region() {
+        let grid = Grid::from_csv(SAMPLE).unwrap();
+        let style = Style { cell_size: 6, outline: Some([9, 9, 9]), ..Style::default() };
+        let frames: Vec<Image> = frames(&grid, &[2, 1], &style).collect();
+        assert_eq!(frames.len(), 3);
+        // After 2 then 1, the region covers (0, 0), (1, 0), (1, 1) and (2, 1).
+        let last = &frames[2];
+        assert_eq!(last.pixel(0, 0), [9, 9, 9]);
+        assert_eq!(last.pixel(6, 6), [9, 9, 9]);
+        assert_eq!(last.pixel(8, 8), render::rgb(1));
+        assert_eq!(last.pixel(9, 3), render::rgb(1));
+        assert_eq!(last.pixel(3, 9), render::rgb(0));
+    }
+
+    #[test]
+    fn test_encoders() {
+        let grid = Grid::from_csv(SAMPLE).unwrap();
+        let style = Style::default();
+        let moves = [2, 1, 2, 0, 1];
+
+        let mut png = Vec::new();
+        write_png(&mut png, &draw(&grid, &|_, _| false, &style)).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+
+        let mut apng = Vec::new();
+        write_apng(&mut apng, frames(&grid, &moves, &style), 200).unwrap();
+        assert!(apng.windows(4).any(|chunk| chunk == b"acTL"));
+        assert_eq!(apng.windows(4).filter(|chunk| chunk == b"fcTL").count(), 6);
+
+        let mut gif = Vec::new();
+        write_gif(&mut gif, &palette(&grid, &style), frames(&grid, &moves, &style), 200).unwrap();
+        assert!(gif.starts_with(b"GIF89a"));
+    }
+
+    #[test]
+    fn test_gif_frames_replay_the_solution() {
+        let grid = Grid::from_csv(SAMPLE).unwrap();
+        let style = Style { cell_size: 3, palette: Vec::new(), outline: Some([0, 0, 0]) };
+        let moves = [2, 1, 2, 0, 1];
+        let mut gif = Vec::new();
+        write_gif(&mut gif, &palette(&grid, &style), frames(&grid, &moves, &style), 200).unwrap();
+
+        let mut decoder = gif::DecodeOptions::new().read_info(gif.as_slice()).unwrap();
+        let global = decoder.global_palette().unwrap().to_vec();
+        let mut canvas = vec![0; 3 * decoder.width() as usize * decoder.height() as usize];
+        let mut cropped = false;
+        for expected in frames(&grid, &moves, &style) {
+            let frame = decoder.read_next_frame().unwrap().unwrap();
+            cropped |= usize::from(frame.width) < expected.width;
+            for (i, &index) in frame.buffer.iter().enumerate() {
+                let (x, y) = (usize::from(frame.left) + i % usize::from(frame.width), usize::from(frame.top) + i / usize::from(frame.width));
+                let at = 3 * (y * expected.width + x);
+                canvas[at..at + 3].copy_from_slice(&global[3 * index as usize..3 * index as usize + 3]);
+            }
+            assert_eq!(canvas, expected.pixels);
+        }
+        assert!(cropped);
+        assert!(decoder.read_next_frame().unwrap().is_none());
+    }
+
+    /// Counts the frames drawn so far, and records how many were drawn
+    /// whenever the encoder writes.
+    struct Probe {
+        drawn: std::rc::Rc<std::cell::Cell<usize>>,
+        at_writes: Vec<usize>,
+    }
+
+    impl Write for Probe {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.at_writes.push(self.drawn.get());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_large_animations_are_streamed() {
+        let grid = crate::generate::generate(40, 40, 6, crate::generate::Pattern::Uniform, 1);
+        let moves = crate::greedy::solve_greedy(&grid, crate::greedy::GreedyScore::Cells, &crate::Budget::unlimited());
+        let style = Style { cell_size: 2, ..Style::default() };
+        let total = moves.len() + 1;
+
+        for gif in [false, true] {
+            let drawn = std::rc::Rc::new(std::cell::Cell::new(0));
+            let counted = frames(&grid, &moves, &style).inspect(|_| drawn.set(drawn.get() + 1));
+            let mut probe = Probe { drawn: drawn.clone(), at_writes: Vec::new() };
+            if gif {
+                write_gif(&mut probe, &palette(&grid, &style), counted, 10).unwrap();
+            } else {
+                write_apng(&mut probe, counted, 10).unwrap();
+            }
+            assert_eq!(drawn.get(), total);
+            // Frames reach the writer while later ones are still to be drawn
+            assert!(probe.at_writes.iter().any(|&n| n < total / 2), "gif: {}", gif);
+        }
+    }
+
+    #[test]
+    fn test_parse_palette() {
+        assert_eq!(parse_palette("#ff0000, 00ff7f").unwrap(), vec![[255, 0, 0], [0, 255, 127]]);
+        assert!(parse_palette("#ff00").is_err());
+        assert!(parse_palette("red").is_err());
+        assert_eq!(hex([255, 0, 127]), "#ff007f");
+    }
+}
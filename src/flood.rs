@@ -0,0 +1,274 @@
+//! Incremental flood fill over the cells of a grid.
+
+use nalgebra::DMatrix;
+
+use crate::topology::Topology;
+use crate::Grid;
+
+/// Flooded region of a grid that grows move after move.
+///
+/// Cells bordering the region are kept in one bucket per colour, so a move
+/// only visits the cells it absorbs instead of walking the whole region from
+/// the origins like `Grid::flood_fill`. Cells outside the region never change
+/// colour, so the original colours are kept and the region's colour is
+/// applied when the grid is rebuilt.
+#[derive(Debug, Clone)]
+pub struct Flood {
+    width: usize,
+    height: usize,
+    colors: usize,
+    topology: Topology,
+    /// Original colour of every cell, row-major.
+    cells: Vec<u8>,
+    walls: Vec<bool>,
+    open_cells: usize,
+    flooded: Vec<bool>,
+    /// Cells that are flooded or sit in a frontier bucket. Walls start out
+    /// reached so that they are never visited.
+    reached: Vec<bool>,
+    /// Cells bordering the region, indexed by colour.
+    frontier: Vec<Vec<usize>>,
+    origins: Vec<(usize, usize)>,
+    /// Colour of each origin before the first move.
+    origin_colors: Vec<u8>,
+    color: u8,
+    /// Whether the origins still have different colours: no move yet.
+    mixed: bool,
+    count: usize,
+}
+
+impl Flood {
+    pub fn new(grid: &Grid) -> Self {
+        let (width, height) = (grid.width(), grid.height());
+        let mut cells = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                cells.push(grid.get(x, y));
+            }
+        }
+        let origins: Vec<usize> = grid.origins().iter().map(|&(x, y)| y * width + x).collect();
+        let origin_colors: Vec<u8> = origins.iter().map(|&i| cells[i]).collect();
+        let mut flood = Flood {
+            width,
+            height,
+            colors: grid.colors(),
+            topology: grid.topology(),
+            color: origin_colors[0],
+            mixed: origin_colors.iter().any(|&c| c != origin_colors[0]),
+            origins: grid.origins().to_vec(),
+            origin_colors,
+            cells,
+            walls: grid.walls.clone(),
+            open_cells: grid.open_cells(),
+            flooded: vec![false; width * height],
+            reached: grid.walls.clone(),
+            frontier: vec![Vec::new(); 256],
+            count: 0,
+        };
+
+        // The component of each origin in its own colour, then its border
+        for &i in &origins {
+            flood.reached[i] = true;
+        }
+        let mut region = Vec::new();
+        for &origin in &origins {
+            let mut stack = vec![origin];
+            while let Some(i) = stack.pop() {
+                region.push(i);
+                for n in flood.neighbours(i) {
+                    if !flood.reached[n] && flood.cells[n] == flood.cells[origin] {
+                        flood.reached[n] = true;
+                        stack.push(n);
+                    }
+                }
+            }
+        }
+        for i in region {
+            flood.flooded[i] = true;
+            flood.count += 1;
+            for n in flood.neighbours(i) {
+                if !flood.reached[n] {
+                    flood.reached[n] = true;
+                    flood.frontier[flood.cells[n] as usize].push(n);
+                }
+            }
+        }
+        flood
+    }
+
+    fn neighbours(&self, i: usize) -> impl Iterator<Item = usize> {
+        let width = self.width;
+        self.topology.neighbours(i % width, i / width, width, self.height).map(move |(x, y)| y * width + x)
+    }
+
+    /// Floods `stack` and every cell of the current colour connected to it.
+    fn absorb(&mut self, mut stack: Vec<usize>) {
+        while let Some(i) = stack.pop() {
+            self.flooded[i] = true;
+            self.count += 1;
+            for n in self.neighbours(i) {
+                if !self.reached[n] {
+                    self.reached[n] = true;
+                    if self.cells[n] == self.color {
+                        stack.push(n);
+                    } else {
+                        self.frontier[self.cells[n] as usize].push(n);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Recolours the region with `color` and absorbs the bordering cells of
+    /// that colour. Returns how many cells were absorbed; playing the current
+    /// colour does nothing.
+    pub fn play(&mut self, color: u8) -> usize {
+        if self.is_noop(color) {
+            return 0;
+        }
+        let before = self.count;
+        self.color = color;
+        self.mixed = false;
+        let stack = std::mem::take(&mut self.frontier[color as usize]);
+        self.absorb(stack);
+        self.count - before
+    }
+
+    /// Current colour of the region: the colour of the first origin.
+    pub fn color(&self) -> u8 {
+        self.color
+    }
+
+    /// Whether playing `color` would change nothing: the whole region
+    /// already has it.
+    pub fn is_noop(&self, color: u8) -> bool {
+        !self.mixed && color == self.color
+    }
+
+    pub fn flooded_cells(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.count == self.open_cells && !self.mixed
+    }
+
+    /// Whether the cell at (x, y) belongs to the region.
+    pub fn is_flooded(&self, x: usize, y: usize) -> bool {
+        self.flooded[y * self.width + x]
+    }
+
+    /// Sorted colours of the cells bordering the region. Before the first
+    /// move, origins of different colours add theirs, since playing one of
+    /// them still recolours the others.
+    pub fn frontier_colors(&self) -> Vec<u8> {
+        (0..=u8::MAX)
+            .filter(|&c| !self.frontier[c as usize].is_empty() || (self.mixed && self.origin_colors.contains(&c)))
+            .collect()
+    }
+
+    /// The grid as `Grid::flood_fill` would have left it.
+    pub fn to_grid(&self) -> Grid {
+        let data = DMatrix::from_fn(self.height, self.width, |y, x| {
+            let i = y * self.width + x;
+            if self.flooded[i] && !self.mixed {
+                self.color
+            } else {
+                self.cells[i]
+            }
+        });
+        Grid {
+            width: self.width,
+            height: self.height,
+            colors: self.colors,
+            data,
+            walls: self.walls.clone(),
+            topology: self.topology,
+            origins: self.origins.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{generate, generate_on, Pattern};
+    use crate::rng::Rng;
+
+    #[test]
+    fn test_matches_flood_fill() {
+        let cases = Topology::NAMES.iter().flat_map(|name| {
+            let topology: Topology = name.parse().unwrap();
+            [(1, Pattern::Uniform), (2, Pattern::Clusters { patch_size: 5 }), (3, Pattern::Stripes)]
+                .map(|(seed, pattern)| (topology, seed, pattern))
+        });
+        for (topology, seed, pattern) in cases {
+            let mut reference = generate_on(topology, 17, 11, 4, pattern, seed);
+            let mut flood = Flood::new(&reference);
+            let mut rng = Rng::new(seed);
+            for _ in 0..40 {
+                let color = rng.below(4) as u8;
+                let before = flood.flooded_cells();
+                let absorbed = flood.play(color);
+                reference.flood_fill(color);
+                assert_eq!(flood.to_grid().data, reference.data);
+                assert_eq!(flood.flooded_cells(), reference.flooded_cells());
+                assert_eq!(flood.flooded_cells(), before + absorbed);
+                assert_eq!(flood.is_complete(), reference.is_complete());
+            }
+        }
+    }
+
+    #[test]
+    fn test_several_origins() {
+        for seed in 0..4 {
+            let mut reference = generate(17, 11, 4, Pattern::Clusters { patch_size: 4 }, seed);
+            reference.set_origins(&[(8, 5), (0, 10), (16, 0)]);
+            let mut flood = Flood::new(&reference);
+            assert_eq!(flood.flooded_cells(), reference.flooded_cells());
+            let mut rng = Rng::new(seed);
+            while !flood.is_complete() {
+                let colors = flood.frontier_colors();
+                let color = colors[rng.below(colors.len())];
+                flood.play(color);
+                reference.flood_fill(color);
+                assert_eq!(flood.to_grid().data, reference.data);
+                assert_eq!(flood.flooded_cells(), reference.flooded_cells());
+            }
+            assert!(reference.is_complete());
+        }
+    }
+
+    #[test]
+    fn test_walls_are_never_flooded() {
+        let mut reference = generate(12, 9, 3, Pattern::Uniform, 4);
+        let mut rng = Rng::new(4);
+        for _ in 0..30 {
+            let (x, y) = (rng.below(12), rng.below(9));
+            let before = reference.clone();
+            reference.set_wall(x, y);
+            if reference.is_wall(0, 0) || reference.unreachable_cell().is_some() {
+                reference = before;
+            }
+        }
+        let mut flood = Flood::new(&reference);
+        while !flood.is_complete() {
+            let color = flood.frontier_colors()[0];
+            flood.play(color);
+            reference.flood_fill(color);
+            assert_eq!(flood.to_grid().to_csv(), reference.to_csv());
+        }
+        assert!(reference.is_complete());
+        assert_eq!(flood.flooded_cells(), reference.open_cells());
+    }
+
+    #[test]
+    fn test_frontier_colors() {
+        let grid = Grid::from_csv("1,2,0,0\n0,1,1,0\n2,2,0,1\n0,0,0,1").unwrap();
+        let mut flood = Flood::new(&grid);
+        assert_eq!(flood.frontier_colors(), vec![0, 2]);
+        assert_eq!(flood.play(2), 1);
+        assert_eq!(flood.play(2), 0);
+        assert_eq!(flood.color(), 2);
+    }
+}
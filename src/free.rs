@@ -0,0 +1,372 @@
+//! Free-Flood-It: every move picks a cell as well as a colour and recolours
+//! the region of that cell, wherever it is. The board is solved once all of it
+//! has a single colour.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::budget::Budget;
+use crate::region::RegionGraph;
+use crate::Grid;
+
+/// Recolours the region holding the cell at `row`, `col`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FreeMove {
+    pub row: usize,
+    pub col: usize,
+    pub color: u8,
+}
+
+/// `row,col,colour`, as in solution files.
+impl fmt::Display for FreeMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{}", self.row, self.col, self.color)
+    }
+}
+
+/// Names accepted by `solve`.
+pub const SOLVER_NAMES: [&str; 2] = ["greedy", "exact"];
+
+/// The components of the initial grid; a position is the colour of each of
+/// them, since moves only ever recolour whole components.
+struct Board {
+    graph: RegionGraph,
+    /// First cell of every component, as (row, col).
+    cells: Vec<(usize, usize)>,
+}
+
+/// Maximal groups of connected components sharing a colour in a position.
+struct Regions {
+    /// Region of every component.
+    of: Vec<usize>,
+    /// Lowest component of every region.
+    first: Vec<usize>,
+    color: Vec<u8>,
+    size: Vec<usize>,
+    adjacency: Vec<Vec<usize>>,
+}
+
+impl Board {
+    fn new(grid: &Grid) -> Self {
+        let graph = RegionGraph::new(grid);
+        let mut cells = vec![(0, 0); graph.component_count()];
+        for (i, &label) in graph.labels.iter().enumerate().rev() {
+            if label != usize::MAX {
+                cells[label] = (i / graph.width, i % graph.width);
+            }
+        }
+        Board { graph, cells }
+    }
+
+    fn regions(&self, colors: &[u8]) -> Regions {
+        let mut regions = Regions { of: vec![usize::MAX; colors.len()], first: Vec::new(), color: Vec::new(), size: Vec::new(), adjacency: Vec::new() };
+        let mut stack = Vec::new();
+        for start in 0..colors.len() {
+            if regions.of[start] != usize::MAX {
+                continue;
+            }
+            let id = regions.first.len();
+            let mut size = 0;
+            regions.of[start] = id;
+            stack.push(start);
+            while let Some(c) = stack.pop() {
+                size += self.graph.sizes[c];
+                for &n in &self.graph.adjacency[c] {
+                    if regions.of[n] == usize::MAX && colors[n] == colors[start] {
+                        regions.of[n] = id;
+                        stack.push(n);
+                    }
+                }
+            }
+            regions.first.push(start);
+            regions.color.push(colors[start]);
+            regions.size.push(size);
+        }
+        regions.adjacency = vec![Vec::new(); regions.first.len()];
+        for (c, neighbours) in self.graph.adjacency.iter().enumerate() {
+            for &n in neighbours {
+                if regions.of[c] != regions.of[n] {
+                    regions.adjacency[regions.of[c]].push(regions.of[n]);
+                }
+            }
+        }
+        for neighbours in &mut regions.adjacency {
+            neighbours.sort_unstable();
+            neighbours.dedup();
+        }
+        regions
+    }
+
+    /// Recolours the region of `component`.
+    fn play(&self, colors: &mut [u8], component: usize, color: u8) {
+        let old = colors[component];
+        let mut stack = vec![component];
+        colors[component] = color;
+        while let Some(c) = stack.pop() {
+            for &n in &self.graph.adjacency[c] {
+                if colors[n] == old && old != color {
+                    colors[n] = color;
+                    stack.push(n);
+                }
+            }
+        }
+    }
+
+    fn to_move(&self, component: usize, color: u8) -> FreeMove {
+        let (row, col) = self.cells[component];
+        FreeMove { row, col, color }
+    }
+}
+
+impl Regions {
+    fn distinct_colors(&self) -> usize {
+        let mut seen = [false; 256];
+        self.color.iter().filter(|&&c| !std::mem::replace(&mut seen[c as usize], true)).count()
+    }
+
+    /// Moves still needed at least. Each move removes at most one colour
+    /// from the board, and contracts a region with some of its neighbours,
+    /// which shortens paths between regions by at most two.
+    fn lower_bound(&self) -> usize {
+        let mut diameter = 0;
+        let mut distances = vec![usize::MAX; self.first.len()];
+        let mut queue = std::collections::VecDeque::new();
+        for start in 0..self.first.len() {
+            distances.fill(usize::MAX);
+            distances[start] = 0;
+            queue.push_back(start);
+            while let Some(r) = queue.pop_front() {
+                diameter = diameter.max(distances[r]);
+                for &n in &self.adjacency[r] {
+                    if distances[n] == usize::MAX {
+                        distances[n] = distances[r] + 1;
+                        queue.push_back(n);
+                    }
+                }
+            }
+        }
+        (self.distinct_colors() - 1).max(diameter.div_ceil(2))
+    }
+}
+
+/// Repeatedly plays the move merging the most regions, then growing the
+/// largest region. When walls leave no two regions of different colours
+/// touching, one region takes the most widespread colour.
+pub fn solve_greedy(grid: &Grid) -> Vec<FreeMove> {
+    let board = Board::new(grid);
+    let mut colors = board.graph.component_colors.clone();
+    let mut moves = Vec::new();
+    loop {
+        let regions = board.regions(&colors);
+        if regions.distinct_colors() <= 1 {
+            return moves;
+        }
+        let mut best: Option<((usize, usize), usize, u8)> = None;
+        for (r, neighbours) in regions.adjacency.iter().enumerate() {
+            for color in 0..board.graph.colors as u8 {
+                let merged: Vec<usize> = neighbours.iter().copied().filter(|&n| regions.color[n] == color).collect();
+                if merged.is_empty() {
+                    continue;
+                }
+                let score = (merged.len(), regions.size[r] + merged.iter().map(|&n| regions.size[n]).sum::<usize>());
+                if best.is_none_or(|(best, _, _)| score > best) {
+                    best = Some((score, r, color));
+                }
+            }
+        }
+        let (region, color) = match best {
+            Some((_, region, color)) => (region, color),
+            None => {
+                let mut cells = vec![0; board.graph.colors];
+                for (r, &c) in regions.color.iter().enumerate() {
+                    cells[c as usize] += regions.size[r];
+                }
+                let common = (0..board.graph.colors).max_by_key(|&c| (cells[c], std::cmp::Reverse(c))).unwrap() as u8;
+                (regions.color.iter().position(|&c| c != common).unwrap(), common)
+            }
+        };
+        let component = regions.first[region];
+        moves.push(board.to_move(component, color));
+        board.play(&mut colors, component, color);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FreeSolution {
+    pub moves: Vec<FreeMove>,
+    pub proven_optimal: bool,
+    pub nodes_expanded: usize,
+}
+
+/// Iterative deepening A* over every cell and colour, starting from the
+/// greedy solution. Only small boards finish; when the budget runs out the
+/// best solution found so far is returned.
+pub fn solve_exact(grid: &Grid, table_bytes: usize, budget: &Budget) -> FreeSolution {
+    struct Search<'a> {
+        board: &'a Board,
+        budget: &'a Budget,
+        /// Positions searched this iteration, with the depth they were met at.
+        table: HashMap<Vec<u8>, usize>,
+        table_entries: usize,
+        moves: Vec<FreeMove>,
+        nodes_expanded: usize,
+    }
+
+    impl Search<'_> {
+        fn run(&mut self, colors: &[u8], threshold: usize) -> Result<(), usize> {
+            let regions = self.board.regions(colors);
+            if regions.distinct_colors() <= 1 {
+                return Ok(());
+            }
+            let f = self.moves.len() + regions.lower_bound();
+            if f > threshold {
+                return Err(f);
+            }
+            if self.table.get(colors).is_some_and(|&d| d <= self.moves.len()) {
+                return Err(usize::MAX);
+            }
+            if self.budget.expand() {
+                return Err(usize::MAX);
+            }
+            if self.table.len() >= self.table_entries {
+                self.table.clear();
+            }
+            self.table.insert(colors.to_vec(), self.moves.len());
+            self.nodes_expanded += 1;
+
+            // Merging moves first, the most merging first
+            let mut candidates = Vec::new();
+            for (r, neighbours) in regions.adjacency.iter().enumerate() {
+                for color in (0..self.board.graph.colors as u8).filter(|&c| c != regions.color[r]) {
+                    let merged = neighbours.iter().filter(|&&n| regions.color[n] == color).count();
+                    candidates.push((std::cmp::Reverse(merged), regions.first[r], color));
+                }
+            }
+            candidates.sort_unstable();
+
+            let mut next_threshold = usize::MAX;
+            for (_, component, color) in candidates {
+                let mut next = colors.to_vec();
+                self.board.play(&mut next, component, color);
+                self.moves.push(self.board.to_move(component, color));
+                match self.run(&next, threshold) {
+                    Ok(()) => return Ok(()),
+                    Err(t) => next_threshold = next_threshold.min(t),
+                }
+                self.moves.pop();
+                if self.budget.stop_reason().is_some() {
+                    break;
+                }
+            }
+            Err(next_threshold)
+        }
+    }
+
+    let board = Board::new(grid);
+    let incumbent = solve_greedy(grid);
+    let root = board.graph.component_colors.clone();
+    let mut search = Search {
+        board: &board,
+        budget,
+        table: HashMap::new(),
+        table_entries: table_bytes / (root.len() + 64).max(1),
+        moves: Vec::new(),
+        nodes_expanded: 0,
+    };
+    let mut threshold = board.regions(&root).lower_bound();
+    while threshold < incumbent.len() {
+        search.table.clear();
+        match search.run(&root, threshold) {
+            Ok(()) => {
+                return FreeSolution { moves: search.moves, proven_optimal: true, nodes_expanded: search.nodes_expanded }
+            }
+            Err(usize::MAX) => break,
+            Err(next) => threshold = next,
+        }
+    }
+    let proven_optimal = budget.stop_reason().is_none();
+    FreeSolution { moves: incumbent, proven_optimal, nodes_expanded: search.nodes_expanded }
+}
+
+/// Runs the solver called `name`, one of `SOLVER_NAMES`.
+pub fn solve(name: &str, grid: &Grid, table_bytes: usize, budget: &Budget) -> Option<FreeSolution> {
+    match name {
+        "greedy" => Some(FreeSolution { moves: solve_greedy(grid), proven_optimal: false, nodes_expanded: 0 }),
+        "exact" => Some(solve_exact(grid, table_bytes, budget)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{generate, Pattern};
+    use crate::greedy::GreedyScore;
+    use crate::table::DEFAULT_TABLE_BYTES;
+    use crate::verify::{verify_free, Verdict};
+
+    /// Fewest moves by breadth-first search over whole grids.
+    fn shortest(grid: &Grid) -> usize {
+        let mut seen = std::collections::HashSet::new();
+        let mut layer = vec![grid.clone()];
+        for depth in 0.. {
+            let mut next = Vec::new();
+            for grid in layer {
+                if grid.is_complete() {
+                    return depth;
+                }
+                for y in 0..grid.height() {
+                    for x in 0..grid.width() {
+                        for color in (0..grid.colors() as u8).filter(|&c| !grid.is_wall(x, y) && c != grid.get(x, y)) {
+                            let mut child = grid.clone();
+                            child.flood_fill_at(x, y, color);
+                            if seen.insert(child.to_csv()) {
+                                next.push(child);
+                            }
+                        }
+                    }
+                }
+            }
+            layer = next;
+        }
+        unreachable!()
+    }
+
+    #[test]
+    fn test_exact_is_optimal() {
+        for seed in 0..6 {
+            let grid = generate(3, 3, 3, Pattern::Uniform, seed);
+            let exact = solve_exact(&grid, DEFAULT_TABLE_BYTES, &Budget::unlimited());
+            assert!(exact.proven_optimal);
+            assert_eq!(exact.moves.len(), shortest(&grid), "seed {}", seed);
+            assert_eq!(verify_free(&grid, &exact.moves).verdict, Verdict::Valid);
+        }
+    }
+
+    #[test]
+    fn test_greedy_solves_every_board() {
+        // Walls cutting the board in two, which grid files cannot describe
+        let mut walls = Grid::from_csv("0,0,1\n0,0,1\n2,0,2").unwrap();
+        (0..3).for_each(|y| walls.set_wall(1, y));
+        let moves = solve_greedy(&walls);
+        assert_eq!(moves.len(), 2);
+        assert_eq!(verify_free(&walls, &moves).verdict, Verdict::Valid);
+
+        for seed in 0..4 {
+            let grid = generate(12, 12, 5, Pattern::Clusters { patch_size: 4 }, seed);
+            let greedy = solve_greedy(&grid);
+            assert_eq!(verify_free(&grid, &greedy).verdict, Verdict::Valid);
+            let fixed = crate::greedy::solve_greedy(&grid, GreedyScore::Cells, &Budget::unlimited());
+            assert!(greedy.len() <= fixed.len(), "{} free moves, {} fixed", greedy.len(), fixed.len());
+        }
+        assert!(solve_greedy(&Grid::from_csv("1,1\n1,1").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_move_format() {
+        let grid = Grid::from_csv("0,1\n1,2").unwrap();
+        let moves = solve_greedy(&grid);
+        assert_eq!(moves[0], FreeMove { row: 0, col: 0, color: 1 });
+        assert_eq!(moves[0].to_string(), "0,0,1");
+    }
+}
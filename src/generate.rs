@@ -0,0 +1,160 @@
+//! Random and adversarial puzzle generation. Every layout is reproducible
+//! from its seed.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::rng::Rng;
+use crate::topology::Topology;
+use crate::Grid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// Every cell gets an independent random colour.
+    Uniform,
+    /// Blobs of one colour averaging `patch_size` cells, grown from random seeds.
+    Clusters { patch_size: usize },
+    /// Vertical one-cell stripes cycling through the colours.
+    Stripes,
+    /// Diagonals cycling through the colours; a classic checkerboard with two.
+    Checkerboard,
+}
+
+impl Pattern {
+    /// Pattern names accepted by `FromStr`.
+    pub const NAMES: [&'static str; 4] = ["uniform", "clusters", "stripes", "checkerboard"];
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    /// Parses a pattern name. Clusters default to patches of 8 cells.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(Pattern::Uniform),
+            "clusters" => Ok(Pattern::Clusters { patch_size: 8 }),
+            "stripes" => Ok(Pattern::Stripes),
+            "checkerboard" => Ok(Pattern::Checkerboard),
+            _ => Err(format!("unknown pattern {:?}, expected one of {}", s, Pattern::NAMES.join(", "))),
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Uniform => write!(f, "uniform"),
+            Pattern::Clusters { patch_size } => write!(f, "clusters of {}", patch_size),
+            Pattern::Stripes => write!(f, "stripes"),
+            Pattern::Checkerboard => write!(f, "checkerboard"),
+        }
+    }
+}
+
+/// Builds a `width` x `height` square grid using colours `0..colors`.
+///
+/// # Panics
+///
+/// If a dimension is zero or `colors` is not between 1 and 256.
+pub fn generate(width: usize, height: usize, colors: usize, pattern: Pattern, seed: u64) -> Grid {
+    generate_on(Topology::Square, width, height, colors, pattern, seed)
+}
+
+/// Like `generate`, for a grid with the given topology. Clusters grow along
+/// its adjacency; the other patterns only depend on coordinates.
+pub fn generate_on(topology: Topology, width: usize, height: usize, colors: usize, pattern: Pattern, seed: u64) -> Grid {
+    assert!(width > 0 && height > 0, "grid must not be empty");
+    assert!((1..=256).contains(&colors), "colour count must be between 1 and 256");
+
+    let mut rng = Rng::new(seed);
+    let mut grid = Grid::new(width, height, colors);
+    grid.set_topology(topology);
+    match pattern {
+        Pattern::Uniform => {
+            for y in 0..height {
+                for x in 0..width {
+                    grid.set(x, y, rng.below(colors) as u8);
+                }
+            }
+        }
+        Pattern::Clusters { patch_size } => grow_clusters(&mut grid, patch_size.max(1), &mut rng),
+        Pattern::Stripes => {
+            for y in 0..height {
+                for x in 0..width {
+                    grid.set(x, y, (x % colors) as u8);
+                }
+            }
+        }
+        Pattern::Checkerboard => {
+            for y in 0..height {
+                for x in 0..width {
+                    grid.set(x, y, ((x + y) % colors) as u8);
+                }
+            }
+        }
+    }
+    grid
+}
+
+/// Scatters one seed per `patch_size` cells, then repeatedly paints a random
+/// frontier cell with the colour of a painted neighbour until none is left.
+fn grow_clusters(grid: &mut Grid, patch_size: usize, rng: &mut Rng) {
+    let (width, height) = (grid.width(), grid.height());
+    let mut painted = vec![false; width * height];
+    let mut frontier = Vec::new();
+
+    let seeds = (width * height).div_ceil(patch_size);
+    for _ in 0..seeds {
+        let (x, y) = (rng.below(width), rng.below(height));
+        grid.set(x, y, rng.below(grid.colors()) as u8);
+        if !painted[y * width + x] {
+            painted[y * width + x] = true;
+            frontier.extend(grid.neighbours(x, y));
+        }
+    }
+
+    while !frontier.is_empty() {
+        let (x, y) = frontier.swap_remove(rng.below(frontier.len()));
+        if painted[y * width + x] {
+            continue;
+        }
+        let sources: Vec<(usize, usize)> = grid.neighbours(x, y).filter(|&(nx, ny)| painted[ny * width + nx]).collect();
+        let (sx, sy) = sources[rng.below(sources.len())];
+        grid.set(x, y, grid.get(sx, sy));
+        painted[y * width + x] = true;
+        frontier.extend(grid.neighbours(x, y).filter(|&(nx, ny)| !painted[ny * width + nx]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RegionGraph;
+
+    #[test]
+    fn test_same_seed_same_grid() {
+        for name in Pattern::NAMES {
+            let pattern = name.parse().unwrap();
+            let a = generate(12, 9, 5, pattern, 7);
+            assert_eq!(a.to_csv(), generate(12, 9, 5, pattern, 7).to_csv());
+            assert_eq!((a.width(), a.height()), (12, 9));
+            assert!(a.data().iter().all(|&color| color < 5));
+        }
+        assert_ne!(generate(12, 9, 5, Pattern::Uniform, 1).to_csv(), generate(12, 9, 5, Pattern::Uniform, 2).to_csv());
+    }
+
+    #[test]
+    fn test_clusters_make_larger_regions() {
+        let uniform = generate(30, 30, 4, Pattern::Uniform, 3);
+        let clusters = generate(30, 30, 4, Pattern::Clusters { patch_size: 20 }, 3);
+        assert!(RegionGraph::new(&clusters).component_count() * 2 < RegionGraph::new(&uniform).component_count());
+    }
+
+    #[test]
+    fn test_adversarial_layouts() {
+        let checkerboard = generate(4, 4, 2, Pattern::Checkerboard, 0);
+        assert_eq!(checkerboard.to_csv(), "0,1,0,1\n1,0,1,0\n0,1,0,1\n1,0,1,0\n");
+        let stripes = generate(3, 2, 3, Pattern::Stripes, 0);
+        assert_eq!(Grid::from_csv(&stripes.to_csv()).unwrap().to_csv(), "0,1,2\n0,1,2\n");
+    }
+}
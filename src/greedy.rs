@@ -0,0 +1,194 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+
+use rayon::prelude::*;
+
+use crate::budget::Budget;
+use crate::region::{FloodState, RegionGraph};
+use crate::Grid;
+
+/// What a greedy step tries to maximise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GreedyScore {
+    /// Size of the flooded region after the move.
+    Cells,
+    /// Number of colours wiped off the board by the move, ties broken by cells.
+    ColorClasses,
+}
+
+/// Plays the move with the best immediate score until the grid is flooded.
+pub fn solve_greedy(grid: &Grid, kind: GreedyScore, budget: &Budget) -> Vec<u8> {
+    let graph = RegionGraph::new(grid);
+    complete_greedily(&graph, &graph.initial_state(), kind, budget)
+}
+
+/// Moves the greedy strategy plays from `state` until the grid is flooded,
+/// each counted as a node of `budget`. Scoring a move is cheap, so the line
+/// is played out even once the budget has run out: budgeted strategies use
+/// it to turn their best partial line into a full solution.
+pub fn complete_greedily(graph: &RegionGraph, state: &FloodState, kind: GreedyScore, budget: &Budget) -> Vec<u8> {
+    let mut current = state.clone();
+    let mut moves = Vec::new();
+
+    while !graph.is_complete(&current) {
+        budget.expand();
+        let candidates = graph.frontier_colors(&current).into_iter().map(|color| (color, graph.play(&current, color)));
+        let (color, next) = match kind {
+            GreedyScore::Cells => candidates.max_by_key(|(color, next)| (next.cells, Reverse(*color))),
+            GreedyScore::ColorClasses => {
+                candidates.max_by_key(|(color, next)| (Reverse(graph.colors_remaining(next)), next.cells, Reverse(*color)))
+            }
+        }
+        .expect("an incomplete grid always has a bordering colour");
+        moves.push(color);
+        current = next;
+    }
+
+    moves
+}
+
+/// Best outcome reachable within `depth` further moves, as (finished, moves
+/// to spare, flooded cells): finishing the grid beats any partial flood, and
+/// finishing sooner beats finishing later.
+fn lookahead_value(graph: &RegionGraph, state: &FloodState, depth: usize) -> (bool, usize, usize) {
+    if graph.is_complete(state) {
+        return (true, depth, state.cells);
+    }
+    if depth == 0 {
+        return (false, 0, state.cells);
+    }
+    graph
+        .frontier_colors(state)
+        .into_iter()
+        .map(|color| lookahead_value(graph, &graph.play(state, color), depth - 1))
+        .max()
+        .unwrap_or((false, 0, state.cells))
+}
+
+/// Greedy on cells, but each move is judged by the best flood reachable
+/// `depth` moves later. Only the first move of the best line is played.
+/// Once the budget runs out the remaining moves are plain greedy.
+pub fn solve_lookahead(grid: &Grid, depth: usize, budget: &Budget) -> Vec<u8> {
+    let depth = depth.max(1);
+    let graph = RegionGraph::new(grid);
+    let mut current = graph.initial_state();
+    let mut moves = Vec::new();
+
+    while !graph.is_complete(&current) {
+        if budget.expand() {
+            moves.extend(complete_greedily(&graph, &current, GreedyScore::Cells, budget));
+            break;
+        }
+        let (color, next) = graph
+            .frontier_colors(&current)
+            .into_iter()
+            .map(|color| (color, graph.play(&current, color)))
+            .max_by_key(|(color, next)| (lookahead_value(&graph, next, depth - 1), Reverse(*color)))
+            .expect("an incomplete grid always has a bordering colour");
+        moves.push(color);
+        current = next;
+    }
+
+    moves
+}
+
+/// Beam search keeping the `width` largest floods at each depth. Beam
+/// entries are expanded in parallel. When the budget runs out, the largest
+/// flood in the beam is finished greedily.
+pub fn solve_beam(grid: &Grid, width: usize, budget: &Budget) -> Vec<u8> {
+    let width = width.max(1);
+    let graph = RegionGraph::new(grid);
+    let mut beam = vec![(graph.initial_state(), Vec::new())];
+
+    loop {
+        if let Some((_, moves)) = beam.iter().find(|(state, _)| graph.is_complete(state)) {
+            return moves.clone();
+        }
+        if budget.expand() {
+            let (state, mut moves) = beam.swap_remove(0);
+            moves.extend(complete_greedily(&graph, &state, GreedyScore::Cells, budget));
+            return moves;
+        }
+
+        let expanded: Vec<Vec<(FloodState, Vec<u8>)>> = beam
+            .par_iter()
+            .map(|(state, moves)| {
+                graph
+                    .frontier_colors(state)
+                    .into_iter()
+                    .map(|color| {
+                        let mut next_moves = moves.clone();
+                        next_moves.push(color);
+                        (graph.play(state, color), next_moves)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // Deduplicate in beam order so the outcome does not depend on the pool size.
+        let mut seen: HashSet<FloodState> = HashSet::new();
+        let mut next_beam: Vec<(FloodState, Vec<u8>)> = expanded
+            .into_iter()
+            .flatten()
+            .filter(|(state, _)| seen.insert(state.clone()))
+            .collect();
+
+        // Stable sort keeps expansion order among ties, so results are reproducible.
+        next_beam.sort_by_key(|(state, _)| Reverse(state.cells));
+        next_beam.truncate(width);
+        beam = next_beam;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::Limits;
+
+    const SAMPLE: &str = "1,2,0,0\n0,1,1,0\n2,2,0,1\n0,0,0,1";
+    const MEDIUM: &str = "2,1,3,0,4\n1,2,2,3,1\n0,3,1,2,4\n4,1,0,3,2\n3,2,4,1,0";
+
+    #[test]
+    fn test_greedy_solutions_are_valid() {
+        for input in [SAMPLE, MEDIUM] {
+            let grid = Grid::from_csv(input).unwrap();
+            for kind in [GreedyScore::Cells, GreedyScore::ColorClasses] {
+                let moves = solve_greedy(&grid, kind, &Budget::unlimited());
+                assert!(grid.clone().apply_solution(&moves));
+            }
+        }
+    }
+
+    #[test]
+    fn test_lookahead_and_beam_are_valid() {
+        let grid = Grid::from_csv(MEDIUM).unwrap();
+        assert!(grid.clone().apply_solution(&solve_lookahead(&grid, 3, &Budget::unlimited())));
+        assert!(grid.clone().apply_solution(&solve_beam(&grid, 8, &Budget::unlimited())));
+    }
+
+    #[test]
+    fn test_deep_lookahead_finishes_soonest() {
+        let grid = Grid::from_csv("0,1\n1,0").unwrap();
+        assert_eq!(solve_lookahead(&grid, 300, &Budget::unlimited()), vec![1, 0]);
+        let grid = Grid::from_csv(SAMPLE).unwrap();
+        assert_eq!(solve_lookahead(&grid, 300, &Budget::unlimited()).len(), 4);
+    }
+
+    #[test]
+    fn test_wide_beam_finds_optimum_on_sample() {
+        let grid = Grid::from_csv(SAMPLE).unwrap();
+        assert_eq!(solve_beam(&grid, 64, &Budget::unlimited()).len(), 4);
+    }
+
+    #[test]
+    fn test_exhausted_budget_still_yields_a_solution() {
+        let grid = Grid::from_csv(MEDIUM).unwrap();
+        let greedy = solve_greedy(&grid, GreedyScore::Cells, &Budget::unlimited()).len();
+        let budget = Budget::new(&Limits { nodes: Some(1), ..Limits::default() });
+        for moves in [solve_beam(&grid, 8, &budget), solve_lookahead(&grid, 3, &budget), solve_greedy(&grid, GreedyScore::Cells, &budget)] {
+            assert!(grid.is_solution(&moves));
+            assert!(moves.len() <= greedy, "{} moves, greedy needs {}", moves.len(), greedy);
+        }
+        assert!(budget.stop_reason().is_some());
+    }
+}
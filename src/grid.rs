@@ -0,0 +1,580 @@
+use std::fmt;
+
+use nalgebra::DMatrix;
+
+use crate::error::ParseError;
+use crate::flood::Flood;
+use crate::topology::Topology;
+
+/// A square (or rectangular) board of coloured cells. The flood starts from
+/// its origins, the top-left cell unless configured otherwise, and spreads to
+/// the cells its `Topology` makes adjacent.
+///
+/// With several origins, every move recolours the regions connected to all
+/// of them at once. Until the first move, origins of different colours each
+/// keep their own.
+///
+/// Cells can be walls: they have no colour, are never flooded and cannot be
+/// crossed, which also lets boards take non-rectangular shapes.
+#[derive(Debug, Clone)]
+pub struct Grid {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) colors: usize,
+    pub(crate) data: DMatrix<u8>,  // 2D matrix to represent the grid
+    pub(crate) walls: Vec<bool>,  // Row-major, true for wall cells
+    pub(crate) topology: Topology,
+    pub(crate) origins: Vec<(usize, usize)>,  // Starting cells as (x, y), never empty
+}
+
+impl Grid {
+    pub fn new(width: usize, height: usize, colors: usize) -> Self {
+        Grid {
+            width,
+            height,
+            colors,
+            data: DMatrix::from_element(height, width, 0),  // Initialize with default color (0)
+            walls: vec![false; width * height],
+            topology: Topology::default(),
+            origins: vec![(0, 0)],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Number of colours in use: one more than the largest colour index, walls
+    /// aside.
+    pub fn colors(&self) -> usize {
+        self.colors
+    }
+
+    /// Colour of the cell at column `x`, row `y`.
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.data[(y, x)]
+    }
+
+    /// Paints the cell at column `x`, row `y`, growing the colour count if
+    /// needed. A wall painted over becomes an ordinary cell.
+    pub fn set(&mut self, x: usize, y: usize, color: u8) {
+        self.data[(y, x)] = color;
+        self.walls[y * self.width + x] = false;
+        self.colors = self.colors.max(color as usize + 1);
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+    }
+
+    /// Cells the flood starts from, as `(x, y)`.
+    pub fn origins(&self) -> &[(usize, usize)] {
+        &self.origins
+    }
+
+    /// Replaces the starting cells, dropping duplicates.
+    ///
+    /// # Panics
+    ///
+    /// If `origins` is empty or a cell lies outside the grid.
+    pub fn set_origins(&mut self, origins: &[(usize, usize)]) {
+        assert!(!origins.is_empty(), "a grid needs at least one origin");
+        assert!(origins.iter().all(|&(x, y)| x < self.width && y < self.height), "origin outside the grid");
+        self.origins.clear();
+        for &origin in origins {
+            if !self.origins.contains(&origin) {
+                self.origins.push(origin);
+            }
+        }
+    }
+
+    /// Parses origins written as in the `start` directive: `x,y` pairs
+    /// separated by `;`. The error is the first entry that is not a pair.
+    pub fn parse_origins(value: &str) -> Result<Vec<(usize, usize)>, String> {
+        value
+            .split(';')
+            .map(|pair| {
+                let (x, y) = pair.split_once(',').ok_or_else(|| pair.trim().to_string())?;
+                match (x.trim().parse(), y.trim().parse()) {
+                    (Ok(x), Ok(y)) => Ok((x, y)),
+                    _ => Err(pair.trim().to_string()),
+                }
+            })
+            .collect()
+    }
+
+    /// Colour of the first origin, which every origin shares after a move.
+    pub fn origin_color(&self) -> u8 {
+        let (x, y) = self.origins[0];
+        self.get(x, y)
+    }
+
+    pub fn is_wall(&self, x: usize, y: usize) -> bool {
+        self.walls[y * self.width + x]
+    }
+
+    /// Turns the cell at column `x`, row `y` into a wall.
+    pub fn set_wall(&mut self, x: usize, y: usize) {
+        self.data[(y, x)] = 0;
+        self.walls[y * self.width + x] = true;
+    }
+
+    /// Number of cells that are not walls.
+    pub fn open_cells(&self) -> usize {
+        self.walls.iter().filter(|&&wall| !wall).count()
+    }
+
+    /// First cell, in row-major order, that no sequence of moves can flood
+    /// because walls cut it off from every origin. Origins that are walls
+    /// reach nothing.
+    pub fn unreachable_cell(&self) -> Option<(usize, usize)> {
+        let mut reached = vec![false; self.width * self.height];
+        let mut stack: Vec<(usize, usize)> = self.origins.iter().copied().filter(|&(x, y)| !self.is_wall(x, y)).collect();
+        for &(x, y) in &stack {
+            reached[y * self.width + x] = true;
+        }
+        while let Some((x, y)) = stack.pop() {
+            for (nx, ny) in self.neighbours(x, y) {
+                if !reached[ny * self.width + nx] {
+                    reached[ny * self.width + nx] = true;
+                    stack.push((nx, ny));
+                }
+            }
+        }
+        (0..self.width * self.height)
+            .find(|&i| !self.walls[i] && !reached[i])
+            .map(|i| (i % self.width, i / self.width))
+    }
+
+    pub fn data(&self) -> &DMatrix<u8> {
+        &self.data
+    }
+
+    /// Plays `solution` on the grid and reports whether it floods it.
+    pub fn is_solution(&self, solution: &[u8]) -> bool {
+        let mut flood = Flood::new(self);
+        for &color in solution {
+            flood.play(color);
+        }
+        flood.is_complete()
+    }
+
+    /// Plays `solution` in place, leaving the grid as repeated `flood_fill`
+    /// calls would, and reports whether it is flooded.
+    pub fn apply_solution(&mut self, solution: &[u8]) -> bool {
+        let mut flood = Flood::new(self);
+        for &color in solution {
+            flood.play(color);
+        }
+        *self = flood.to_grid();
+        flood.is_complete()
+    }
+
+    pub fn print_stats(&self) {
+        println!("Grid Statistics:");
+        println!("  Size: {}x{}", self.width, self.height);
+        println!("  Cells: {}", self.open_cells());
+        if self.open_cells() < self.width * self.height {
+            println!("  Walls: {}", self.width * self.height - self.open_cells());
+        }
+        println!("  Colors: {}", self.colors);
+        println!("  Search Space: {}", (self.colors as f64).powf(self.open_cells() as f64));
+    }
+
+    /// Parses comma-separated rows of colour indices, with `#` or `-1` for
+    /// walls. Every row must have as many cells as the first one. Blank lines
+    /// around the grid are ignored. Boards where walls cut cells off from
+    /// every origin are rejected.
+    ///
+    /// The rows may be preceded by `key: value` directives, one per line:
+    /// `start` lists the origins as `x,y` pairs separated by `;` (`0,0` by
+    /// default, columns and rows counted from 0), and `topology` names a
+    /// `Topology` (`square` by default). For
+    /// `hex`, each CSV row is a row of hexagons and every second row is
+    /// shifted half a cell to the right, so a cell touches two cells of the
+    /// row above and two of the row below. For `hex-axial`, columns and rows
+    /// are the axial `q` and `r` coordinates and the board is a rhombus:
+    ///
+    /// ```text
+    /// topology: hex
+    /// 0,1,1
+    /// 2,0,1
+    /// 1,2,2
+    /// ```
+    pub fn from_csv(content: &str) -> Result<Self, ParseError> {
+        let lines: Vec<(usize, &str)> = content.lines().enumerate().map(|(i, l)| (i + 1, l.trim())).collect();
+
+        let mut topology = Topology::default();
+        let mut origins = None;
+        let mut start = 0;
+        while let Some(&(line, text)) = lines.get(start) {
+            if !text.is_empty() {
+                if !text.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                    break;
+                }
+                let (key, value) = text.split_once(':').unwrap_or((text, ""));
+                match (key.trim(), value.trim()) {
+                    ("start", value) => {
+                        let parsed = Grid::parse_origins(value).map_err(|value| ParseError::InvalidStart { line, value })?;
+                        origins = Some((line, parsed));
+                    }
+                    ("topology", name) => {
+                        topology = name
+                            .parse()
+                            .map_err(|_| ParseError::UnknownTopology { line, name: name.to_string() })?
+                    }
+                    (key, _) => return Err(ParseError::UnknownDirective { line, key: key.to_string() }),
+                }
+            }
+            start += 1;
+        }
+        let lines = &lines[start..];
+
+        let first = lines.iter().position(|(_, l)| !l.is_empty()).ok_or(ParseError::Empty)?;
+        let last = lines.iter().rposition(|(_, l)| !l.is_empty()).ok_or(ParseError::Empty)?;
+        let rows = &lines[first..=last];
+
+        let height = rows.len();
+        let width = rows[0].1.split(',').count(); // Get the number of columns from the first row
+
+        let mut data = DMatrix::zeros(height, width);
+        let mut walls = vec![false; width * height];
+        let mut colors = 0;
+
+        for (i, &(line, row)) in rows.iter().enumerate() {
+            let cells: Vec<&str> = row.split(',').map(str::trim).collect();
+            if cells.len() != width {
+                return Err(ParseError::RaggedRow { line, expected: width, actual: cells.len() });
+            }
+            for (j, token) in cells.into_iter().enumerate() {
+                let column = j + 1;
+                if token == "#" || token == "-1" {
+                    walls[i * width + j] = true;
+                } else if !token.is_empty() && token.bytes().all(|b| b.is_ascii_digit()) {
+                    let color = token.parse::<u8>().map_err(|_| ParseError::ColorOutOfRange {
+                        line,
+                        column,
+                        token: token.to_string(),
+                    })?;
+                    colors = colors.max(color as usize + 1);
+                    data[(i, j)] = color;
+                } else {
+                    return Err(ParseError::InvalidColor { line, column, token: token.to_string() });
+                }
+            }
+        }
+
+        let mut grid = Grid { width, height, colors, data, walls, topology, origins: vec![(0, 0)] };
+        if let Some((line, origins)) = origins {
+            if let Some(&(x, y)) = origins.iter().find(|&&(x, y)| x >= width || y >= height) {
+                return Err(ParseError::StartOutOfBounds { line, x, y });
+            }
+            grid.set_origins(&origins);
+        }
+        if let Some(&(x, y)) = grid.origins.iter().find(|&&(x, y)| grid.is_wall(x, y)) {
+            return Err(ParseError::WallAtOrigin { line: rows[y].0, column: x + 1 });
+        }
+        if let Some((x, y)) = grid.unreachable_cell() {
+            return Err(ParseError::Unreachable { line: rows[y].0, column: x + 1 });
+        }
+        Ok(grid)
+    }
+
+    /// Like `from_csv`, but also rejects grids that are not square.
+    pub fn from_square_csv(content: &str) -> Result<Self, ParseError> {
+        let grid = Grid::from_csv(content)?;
+        if grid.width != grid.height {
+            return Err(ParseError::NotSquare { width: grid.width, height: grid.height });
+        }
+        Ok(grid)
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut result = String::new();
+        if self.topology != Topology::default() {
+            result.push_str(&format!("topology: {}\n", self.topology));
+        }
+        if self.origins != [(0, 0)] {
+            let origins: Vec<String> = self.origins.iter().map(|(x, y)| format!("{},{}", x, y)).collect();
+            result.push_str(&format!("start: {}\n", origins.join("; ")));
+        }
+        for i in 0..self.height {
+            for j in 0..self.width {
+                if j > 0 {
+                    result.push(',');
+                }
+                if self.walls[i * self.width + j] {
+                    result.push('#');
+                } else {
+                    result.push_str(&self.data[(i, j)].to_string());
+                }
+            }
+            result.push('\n');
+        }
+        result
+    }
+
+    /// Recolours the region connected to the origins with `target_color`,
+    /// walking it from scratch. `Flood` gives the same grids incrementally and
+    /// is what replays use; this is kept as the reference implementation.
+    pub fn flood_fill(&mut self, target_color: u8) {
+        // Ensure grid dimensions are valid
+        assert!(self.width > 0, "Width must be greater than zero");
+        assert!(self.height > 0, "Height must be greater than zero");
+
+        // If every origin already has the target color, no need to do anything
+        if self.origins.iter().all(|&(x, y)| self.data[(y, x)] == target_color) {
+            return;
+        }
+
+        for (y, row) in self.region().into_iter().enumerate() {
+            for (x, flooded) in row.into_iter().enumerate() {
+                if flooded {
+                    self.data[(y, x)] = target_color;
+                }
+            }
+        }
+    }
+
+    /// Recolours the region holding the cell at `(x, y)` with `target_color`,
+    /// the Free-Flood-It move, and returns its size. Walls are left alone.
+    pub fn flood_fill_at(&mut self, x: usize, y: usize, target_color: u8) -> usize {
+        if self.is_wall(x, y) {
+            return 0;
+        }
+        let mut size = 0;
+        for (y, row) in self.region_from(&[(x, y)]).into_iter().enumerate() {
+            for (x, flooded) in row.into_iter().enumerate() {
+                if flooded {
+                    self.data[(y, x)] = target_color;
+                    size += 1;
+                }
+            }
+        }
+        self.colors = self.colors.max(target_color as usize + 1);
+        size
+    }
+
+    /// Cells connected to an origin through cells of that origin's colour.
+    fn region(&self) -> Vec<Vec<bool>> {
+        self.region_from(&self.origins)
+    }
+
+    fn region_from(&self, origins: &[(usize, usize)]) -> Vec<Vec<bool>> {
+        // Create a visited set to avoid revisiting cells
+        let mut visited = vec![vec![false; self.width]; self.height];
+
+        for &(ox, oy) in origins {
+            let source_color = self.data[(oy, ox)];
+            if visited[oy][ox] || self.is_wall(ox, oy) {
+                continue;
+            }
+
+            // Use a stack to implement depth-first search (DFS)
+            let mut stack = vec![(ox, oy)];
+            visited[oy][ox] = true;
+            while let Some((x, y)) = stack.pop() {
+                // Every neighbour of the source color, as the topology defines them
+                for (nx, ny) in self.neighbours(x, y) {
+                    if !visited[ny][nx] && self.data[(ny, nx)] == source_color {
+                        visited[ny][nx] = true;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+        }
+        visited
+    }
+
+    /// Cells the topology makes adjacent to `(x, y)`, walls aside.
+    pub fn neighbours(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.topology
+            .neighbours(x, y, self.width, self.height)
+            .filter(move |&(nx, ny)| !self.is_wall(nx, ny))
+    }
+
+    /// Number of cells in the flooded region: those connected to an origin
+    /// through cells of its colour.
+    pub fn flooded_cells(&self) -> usize {
+        self.region().iter().flatten().filter(|&&flooded| flooded).count()
+    }
+
+    /// Whether every cell but the walls has the same colour.
+    pub fn is_complete(&self) -> bool {
+        let target = self.origin_color();
+        (0..self.height).all(|y| (0..self.width).all(|x| self.is_wall(x, y) || self.data[(y, x)] == target))
+    }
+}
+
+/// Rows of space-separated colours, with `#` for walls. Hexagonal rows are
+/// indented to show which cells touch.
+impl fmt::Display for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cell_width = (self.colors.max(1) - 1).to_string().len();
+        for y in 0..self.height {
+            let indent = self.topology.row_shift(y) * (cell_width + 1) / 2;
+            write!(f, "{:indent$}", "", indent = indent)?;
+            for x in 0..self.width {
+                if x > 0 {
+                    f.write_str(" ")?;
+                }
+                if self.is_wall(x, y) {
+                    write!(f, "{:>w$}", "#", w = cell_width)?;
+                } else {
+                    write!(f, "{:>w$}", self.data[(y, x)], w = cell_width)?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flood_fill() {
+        let mut grid = Grid::new(3, 3, 3);
+        let original = DMatrix::from_vec(3, 3, vec![0, 0, 0, 1, 1, 1, 1, 0, 0]);
+        grid.data = original.clone();
+        grid.flood_fill(2);
+        let expected = DMatrix::from_vec(3, 3, vec![2, 2, 2, 1, 1, 1, 1, 0, 0]);
+
+        assert_eq!(grid.data, expected);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Grid::from_csv(" \n\n").unwrap_err(), ParseError::Empty);
+        assert_eq!(
+            Grid::from_csv("0,1\n1,1,2\n").unwrap_err(),
+            ParseError::RaggedRow { line: 2, expected: 2, actual: 3 }
+        );
+        assert_eq!(
+            Grid::from_csv("\n0,1\n1,x\n").unwrap_err(),
+            ParseError::InvalidColor { line: 3, column: 2, token: "x".to_string() }
+        );
+        assert_eq!(
+            Grid::from_csv("0,-2\n1,1").unwrap_err(),
+            ParseError::InvalidColor { line: 1, column: 2, token: "-2".to_string() }
+        );
+        assert_eq!(
+            Grid::from_csv("0,1\n256,1").unwrap_err(),
+            ParseError::ColorOutOfRange { line: 2, column: 1, token: "256".to_string() }
+        );
+        assert_eq!(
+            Grid::from_square_csv("0,1,2\n1,1,2").unwrap_err(),
+            ParseError::NotSquare { width: 3, height: 2 }
+        );
+    }
+
+    #[test]
+    fn test_walls() {
+        let mut grid = Grid::from_csv("0,1,#\n1,-1,2\n0,0,2").unwrap();
+        assert!(grid.is_wall(2, 0) && grid.is_wall(1, 1));
+        assert_eq!((grid.open_cells(), grid.colors()), (7, 3));
+        assert_eq!(grid.to_csv(), "0,1,#\n1,#,2\n0,0,2\n");
+        assert_eq!(grid.to_string(), "0 1 #\n1 # 2\n0 0 2\n");
+
+        grid.flood_fill(1);
+        assert_eq!(grid.to_csv(), "1,1,#\n1,#,2\n0,0,2\n");
+        assert!(grid.apply_solution(&[0, 2]));
+        assert_eq!(grid.to_csv(), "2,2,#\n2,#,2\n2,2,2\n");
+    }
+
+    #[test]
+    fn test_unreachable_cells_are_rejected() {
+        assert_eq!(Grid::from_csv("#,0\n0,1").unwrap_err(), ParseError::WallAtOrigin { line: 1, column: 1 });
+        assert_eq!(
+            Grid::from_csv("\n0,#,1\n#,1,1").unwrap_err(),
+            ParseError::Unreachable { line: 2, column: 3 }
+        );
+    }
+
+    #[test]
+    fn test_topology_directive() {
+        let grid = Grid::from_csv("topology: hex\n\n0,1,1\n2,0,1\n1,2,2").unwrap();
+        assert_eq!(grid.topology(), Topology::Hex);
+        assert_eq!(grid.to_csv(), "topology: hex\n0,1,1\n2,0,1\n1,2,2\n");
+        assert_eq!(grid.to_string(), "0 1 1\n 2 0 1\n1 2 2\n");
+        assert_eq!(
+            Grid::from_csv("topology: klein\n0,1").unwrap_err(),
+            ParseError::UnknownTopology { line: 1, name: "klein".to_string() }
+        );
+        assert_eq!(
+            Grid::from_csv("goal: 1\n0,1").unwrap_err(),
+            ParseError::UnknownDirective { line: 1, key: "goal".to_string() }
+        );
+    }
+
+    #[test]
+    fn test_start_directive() {
+        let mut grid = Grid::from_csv("start: 1,1\n0,1,0\n1,2,1\n0,1,0").unwrap();
+        assert_eq!(grid.origins(), [(1, 1)]);
+        assert_eq!(grid.to_csv(), "start: 1,1\n0,1,0\n1,2,1\n0,1,0\n");
+        grid.flood_fill(1);
+        assert_eq!(grid.flooded_cells(), 5);
+        grid.flood_fill(0);
+        assert!(grid.is_complete());
+
+        let grid = Grid::from_csv("start: 0,0; 2,2 ;0,0\n0,1,0\n1,0,1\n0,1,1").unwrap();
+        assert_eq!(grid.origins(), [(0, 0), (2, 2)]);
+        assert_eq!(grid.flooded_cells(), 4);
+        assert_eq!(Grid::from_csv(&grid.to_csv()).unwrap().origins(), grid.origins());
+
+        assert_eq!(
+            Grid::from_csv("start: 1;2\n0,1").unwrap_err(),
+            ParseError::InvalidStart { line: 1, value: "1".to_string() }
+        );
+        assert_eq!(Grid::from_csv("start: 2,0\n0,1").unwrap_err(), ParseError::StartOutOfBounds { line: 1, x: 2, y: 0 });
+        assert_eq!(
+            Grid::from_csv("start: 0,0; 1,1\n0,1\n0,#").unwrap_err(),
+            ParseError::WallAtOrigin { line: 3, column: 2 }
+        );
+        // Each origin reaches the cells on its side of the wall.
+        assert!(Grid::from_csv("start: 0,0; 2,0\n0,#,1\n0,#,1").is_ok());
+        assert!(Grid::from_csv("0,#,1\n0,#,1").is_err());
+    }
+
+    #[test]
+    fn test_mixed_origins_need_a_move() {
+        let mut grid = Grid::from_csv("start: 0,0; 1,0\n0,1\n0,1").unwrap();
+        assert_eq!(grid.flooded_cells(), 4);
+        assert!(!grid.is_complete() && !grid.is_solution(&[]));
+        assert!(grid.is_solution(&[0]));
+        grid.flood_fill(0);
+        assert!(grid.is_complete());
+    }
+
+    #[test]
+    fn test_flood_fill_follows_the_topology() {
+        // Cells flooded before and after playing 1.
+        let input = "0,1,0\n1,0,1\n0,1,0";
+        for (name, before, after) in
+            [("square", 1, 3), ("square8", 5, 9), ("torus", 4, 8), ("hex", 1, 4), ("hex-axial", 1, 3)]
+        {
+            let mut grid = Grid::from_csv(&format!("topology: {}\n{}", name, input)).unwrap();
+            assert_eq!(grid.flooded_cells(), before, "{}", name);
+            grid.flood_fill(1);
+            assert_eq!(grid.flooded_cells(), after, "{}", name);
+            assert_eq!(grid.flooded_cells(), Flood::new(&grid).flooded_cells(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_parse_accepts_crlf_and_padding() {
+        let grid = Grid::from_square_csv("0, 1\r\n1 ,2\r\n\n").unwrap();
+        assert_eq!((grid.width(), grid.height(), grid.colors()), (2, 2, 3));
+        assert_eq!(grid.get(1, 1), 2);
+    }
+}
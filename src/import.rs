@@ -0,0 +1,366 @@
+//! Grids read back from pictures of a board: decoding, finding the cell
+//! lattice, sampling the cells and clustering their colours.
+
+use std::io::Read;
+
+use crate::export::Image;
+use crate::Grid;
+
+/// Distance between two colours, in RGB units.
+fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum::<f64>().sqrt()
+}
+
+fn to_f64(rgb: [u8; 3]) -> [f64; 3] {
+    rgb.map(f64::from)
+}
+
+fn to_u8(rgb: [f64; 3]) -> [u8; 3] {
+    rgb.map(|c| c.round().clamp(0.0, 255.0) as u8)
+}
+
+/// Decodes a PNG into RGB, dropping any transparency.
+pub fn decode_png<R: Read>(reader: R) -> Result<Image, String> {
+    let mut decoder = png::Decoder::new(reader);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|err| err.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|err| err.to_string())?;
+    let (width, height) = (info.width as usize, info.height as usize);
+    let channels = info.color_type.samples();
+    let mut pixels = Vec::with_capacity(3 * width * height);
+    for row in buffer.chunks(info.line_size).take(height) {
+        for pixel in row.chunks_exact(channels).take(width) {
+            match info.color_type {
+                png::ColorType::Grayscale | png::ColorType::GrayscaleAlpha => pixels.extend_from_slice(&[pixel[0]; 3]),
+                _ => pixels.extend_from_slice(&pixel[..3]),
+            }
+        }
+    }
+    Ok(Image { width, height, pixels })
+}
+
+/// Colours closer than this count as the same when looking for cell edges.
+const EDGE_THRESHOLD: f64 = 24.0;
+
+/// For every pixel column (or row when `vertical`) past the first, how many
+/// pixel rows change colour there.
+fn edge_profile(image: &Image, vertical: bool) -> Vec<usize> {
+    let (length, across) = if vertical { (image.height, image.width) } else { (image.width, image.height) };
+    let pixel = |along: usize, at: usize| to_f64(if vertical { image.pixel(at, along) } else { image.pixel(along, at) });
+    let mut profile = vec![0; length];
+    for (along, count) in profile.iter_mut().enumerate().skip(1) {
+        *count = (0..across).filter(|&at| distance(pixel(along, at), pixel(along - 1, at)) > EDGE_THRESHOLD).count();
+    }
+    profile
+}
+
+/// Colour changes at most this many pixels apart are taken for the two
+/// sides of one grid line.
+const LINE_WIDTH: usize = 3;
+
+/// Number of cells along a profile: the fewest whose boundaries, give or
+/// take a quarter of a cell, account for nearly all the colour changes.
+/// Multiples of the true count account for them too, but no smaller count
+/// does. The changes on both sides of a grid line count as one in between,
+/// and those of a frame around the board are left out.
+fn cells_along(profile: &[usize]) -> Option<usize> {
+    let length = profile.len();
+    // Weighted position of every edge or grid line
+    let mut edges: Vec<(f64, usize)> = Vec::new();
+    let mut start = None;
+    let inner = LINE_WIDTH + 1..length.saturating_sub(LINE_WIDTH);
+    for (i, &count) in profile.iter().enumerate().filter(|&(i, &count)| count > 0 && inner.contains(&i)) {
+        match edges.last_mut() {
+            Some((position, weight)) if start.is_some_and(|start| i - start <= LINE_WIDTH) => {
+                *position = (*position * *weight as f64 + (i * count) as f64) / (*weight + count) as f64;
+                *weight += count;
+            }
+            _ => {
+                edges.push((i as f64, count));
+                start = Some(i);
+            }
+        }
+    }
+    let total: usize = edges.iter().map(|&(_, weight)| weight).sum();
+    if total == 0 {
+        return None;
+    }
+    (2..=length / (LINE_WIDTH + 1)).find(|&cells| {
+        // Least-squares pitch, in case the picture is cropped a little off
+        let guess = length as f64 / cells as f64;
+        let (moments, squares) = edges.iter().fold((0.0, 0.0), |(moments, squares), &(position, weight)| {
+            let k = (position / guess).round();
+            (moments + weight as f64 * k * position, squares + weight as f64 * k * k)
+        });
+        let pitch = if squares > 0.0 { moments / squares } else { guess };
+        let slack = (pitch / 4.0).max(1.0);
+        let on_boundary: usize = edges
+            .iter()
+            .filter(|&&(position, _)| (position - (position / pitch).round() * pitch).abs() <= slack)
+            .map(|&(_, weight)| weight)
+            .sum();
+        on_boundary as f64 >= 0.95 * total as f64
+    })
+}
+
+/// Width and height in cells of a board picture cropped to the board, found
+/// from where the colour changes line up. `None` when there are no edges.
+pub fn detect_lattice(image: &Image) -> Option<(usize, usize)> {
+    Some((cells_along(&edge_profile(image, false))?, cells_along(&edge_profile(image, true))?))
+}
+
+/// Average colour of the middle third of every cell, row by row, so that
+/// grid lines and anti-aliased borders are left out.
+pub fn sample_cells(image: &Image, width: usize, height: usize) -> Vec<[f64; 3]> {
+    let span = |index: usize, cells: usize, length: usize| {
+        let (start, end) = (index * length / cells, (index + 1) * length / cells);
+        let margin = (end - start) / 3;
+        (start + margin)..(end - margin).max(start + margin + 1)
+    };
+    let mut samples = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0.0; 3];
+            let mut count = 0.0;
+            for py in span(y, height, image.height) {
+                for px in span(x, width, image.width) {
+                    let rgb = to_f64(image.pixel(px, py));
+                    (0..3).for_each(|c| sum[c] += rgb[c]);
+                    count += 1.0;
+                }
+            }
+            samples.push(sum.map(|c| c / count));
+        }
+    }
+    samples
+}
+
+/// Index of the centre nearest to `color`, and its distance.
+fn nearest(centres: &[[f64; 3]], color: [f64; 3]) -> (usize, f64) {
+    centres
+        .iter()
+        .enumerate()
+        .map(|(i, &centre)| (i, distance(centre, color)))
+        .fold((0, f64::INFINITY), |best, candidate| if candidate.1 < best.1 { candidate } else { best })
+}
+
+/// Sample farthest from every centre, and its distance.
+fn farthest(centres: &[[f64; 3]], samples: &[[f64; 3]]) -> (usize, f64) {
+    samples
+        .iter()
+        .map(|&sample| nearest(centres, sample).1)
+        .enumerate()
+        .fold((0, 0.0), |best, candidate| if candidate.1 > best.1 { candidate } else { best })
+}
+
+/// k-means over `samples`. Centres start from the first sample, each next one
+/// being the sample farthest from those chosen so far, until every sample lies
+/// within `tolerance` of one. With `k` set, the `k` centres nearest to the
+/// most samples are kept, or farthest samples added up to `k`. Samples beyond
+/// `tolerance` do not move the centres, so stray cells cannot drag a colour.
+pub fn cluster(samples: &[[f64; 3]], k: Option<usize>, tolerance: f64) -> Vec<[f64; 3]> {
+    let mut centres = vec![samples[0]];
+    loop {
+        let (sample, gap) = farthest(&centres, samples);
+        if gap <= tolerance || centres.len() == 256 {
+            break;
+        }
+        centres.push(samples[sample]);
+    }
+    if let Some(k) = k {
+        let mut members = vec![0; centres.len()];
+        samples.iter().for_each(|&sample| members[nearest(&centres, sample).0] += 1);
+        let mut order: Vec<usize> = (0..centres.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(members[i]));
+        order.truncate(k);
+        order.sort_unstable();
+        centres = order.into_iter().map(|i| centres[i]).collect();
+        loop {
+            let (sample, gap) = farthest(&centres, samples);
+            if gap == 0.0 || centres.len() == k {
+                break;
+            }
+            centres.push(samples[sample]);
+        }
+    }
+
+    for _ in 0..100 {
+        let mut sums = vec![([0.0; 3], 0.0); centres.len()];
+        for &sample in samples {
+            let (centre, distance) = nearest(&centres, sample);
+            if distance <= tolerance {
+                let (sum, count) = &mut sums[centre];
+                (0..3).for_each(|c| sum[c] += sample[c]);
+                *count += 1.0;
+            }
+        }
+        let next: Vec<[f64; 3]> = sums
+            .iter()
+            .zip(&centres)
+            .map(|(&(sum, count), &centre)| if count > 0.0 { sum.map(|c| c / count) } else { centre })
+            .collect();
+        if next == centres {
+            break;
+        }
+        centres = next;
+    }
+    centres
+}
+
+/// A cell whose colour is far from every palette colour.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ambiguous {
+    pub x: usize,
+    pub y: usize,
+    pub rgb: [u8; 3],
+    /// Distance to the nearest palette colour.
+    pub distance: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// Width and height in cells, detected when unset.
+    pub dimensions: Option<(usize, usize)>,
+    /// Number of colours, the fewest keeping every cell within `tolerance`
+    /// of its colour when unset.
+    pub colors: Option<usize>,
+    /// Cells farther than this from their colour are reported ambiguous.
+    pub tolerance: f64,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        ImportOptions { dimensions: None, colors: None, tolerance: 40.0 }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub grid: Grid,
+    /// RGB colour of every colour index.
+    pub palette: Vec<[u8; 3]>,
+    pub ambiguous: Vec<Ambiguous>,
+}
+
+/// Reads a board from a picture cropped to it. Colour indices follow the
+/// order in which colours first appear, row by row.
+pub fn import(image: &Image, options: &ImportOptions) -> Result<Import, String> {
+    let (width, height) = match options.dimensions {
+        Some(dimensions) => dimensions,
+        None => detect_lattice(image).ok_or("cannot find the cell lattice, give the grid dimensions")?,
+    };
+    if width == 0 || height == 0 || width > image.width || height > image.height {
+        return Err(format!("cannot fit {}x{} cells in a {}x{} picture", width, height, image.width, image.height));
+    }
+    if options.colors.is_some_and(|k| k == 0 || k > 256) {
+        return Err("the number of colours must be between 1 and 256".to_string());
+    }
+
+    let samples = sample_cells(image, width, height);
+    let centres = cluster(&samples, options.colors, options.tolerance);
+    let mut index = vec![None; centres.len()];
+    let mut palette = Vec::new();
+    let mut grid = Grid::new(width, height, 0);
+    let mut ambiguous = Vec::new();
+    for (i, &sample) in samples.iter().enumerate() {
+        let (x, y) = (i % width, i / width);
+        let (centre, distance) = nearest(&centres, sample);
+        let color = *index[centre].get_or_insert_with(|| {
+            palette.push(to_u8(centres[centre]));
+            (palette.len() - 1) as u8
+        });
+        grid.set(x, y, color);
+        if distance > options.tolerance {
+            ambiguous.push(Ambiguous { x, y, rgb: to_u8(sample), distance });
+        }
+    }
+    Ok(Import { grid, palette, ambiguous })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{draw, write_png, Style};
+    use crate::generate::{generate, Pattern};
+
+    /// Colours renumbered by first appearance, row by row.
+    fn canonical(grid: &Grid) -> Vec<u8> {
+        let mut seen = Vec::new();
+        let mut cells = Vec::new();
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                let color = grid.get(x, y);
+                if !seen.contains(&color) {
+                    seen.push(color);
+                }
+                cells.push(seen.iter().position(|&c| c == color).unwrap() as u8);
+            }
+        }
+        cells
+    }
+
+    #[test]
+    fn test_round_trip_through_png() {
+        let grid = generate(13, 9, 5, Pattern::Uniform, 7);
+        let style = Style { cell_size: 11, ..Style::default() };
+        let mut file = Vec::new();
+        write_png(&mut file, &draw(&grid, &|_, _| false, &style)).unwrap();
+        let image = decode_png(file.as_slice()).unwrap();
+        assert_eq!(detect_lattice(&image), Some((13, 9)));
+
+        let import = import(&image, &ImportOptions::default()).unwrap();
+        assert_eq!((import.grid.width(), import.grid.height(), import.grid.colors()), (13, 9, 5));
+        assert_eq!(canonical(&import.grid), canonical(&grid));
+        assert_eq!(import.palette[0], crate::render::rgb(grid.get(0, 0)));
+        assert!(import.ambiguous.is_empty());
+    }
+
+    #[test]
+    fn test_imports_256_colours() {
+        let mut grid = Grid::new(16, 16, 0);
+        for i in 0..256 {
+            grid.set(i % 16, i / 16, i as u8);
+        }
+        let palette = (0..256).map(|i| [(i % 8 * 36) as u8, (i / 8 % 8 * 36) as u8, (i / 64 * 85) as u8]).collect();
+        let image = draw(&grid, &|_, _| false, &Style { cell_size: 4, palette, outline: None });
+        let options = ImportOptions { dimensions: Some((16, 16)), colors: Some(256), ..ImportOptions::default() };
+        let import = import(&image, &options).unwrap();
+        assert_eq!(import.palette.len(), 256);
+        assert_eq!(canonical(&import.grid), canonical(&grid));
+    }
+
+    #[test]
+    fn test_far_cells_are_ambiguous() {
+        let grid = generate(6, 6, 3, Pattern::Uniform, 1);
+        let mut image = draw(&grid, &|_, _| false, &Style { cell_size: 8, ..Style::default() });
+        for py in 16..24 {
+            for px in 24..32 {
+                let i = 3 * (py * image.width + px);
+                image.pixels[i..i + 3].copy_from_slice(&[0, 0, 0]);
+            }
+        }
+        let options = ImportOptions { dimensions: Some((6, 6)), colors: Some(3), ..ImportOptions::default() };
+        let import = import(&image, &options).unwrap();
+        assert_eq!(import.ambiguous.len(), 1);
+        assert_eq!((import.ambiguous[0].x, import.ambiguous[0].y, import.ambiguous[0].rgb), (3, 2, [0, 0, 0]));
+
+        // Left free, the black cell gets a colour of its own.
+        let import = super::import(&image, &ImportOptions { colors: None, ..options }).unwrap();
+        assert_eq!(import.grid.colors(), 4);
+        assert!(import.ambiguous.is_empty());
+    }
+
+    #[test]
+    fn test_sampling_skips_grid_lines() {
+        let mut image = Image { width: 20, height: 10, pixels: vec![200; 3 * 200] };
+        for y in 0..10 {
+            for x in [0, 9, 10, 19] {
+                let i = 3 * (y * 20 + x);
+                image.pixels[i..i + 3].copy_from_slice(&[0, 0, 0]);
+            }
+        }
+        assert_eq!(sample_cells(&image, 2, 1), vec![[200.0; 3]; 2]);
+        assert!(import(&image, &ImportOptions { dimensions: Some((21, 1)), ..ImportOptions::default() }).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+//! Local search shortening an existing solution: dropping moves that turn out
+//! to be redundant, replacing runs of moves with shorter ones found by exact
+//! search, and restarting a stronger solver from intermediate states. Every
+//! change is checked by replaying the whole solution with
+//! `Grid::apply_solution`.
+
+use crate::budget::Budget;
+use crate::region::{FloodState, RegionGraph};
+use crate::solver::{Solver, SolverOptions};
+use crate::Grid;
+
+/// How hard `improve` tries.
+pub struct ImproveOptions<'a> {
+    /// Longest run of moves replaced by an exact search. Each run costs up to
+    /// `colours ^ (window - 1)` nodes.
+    pub window: usize,
+    /// Solver restarted from the states the solution goes through, if any.
+    pub solver: Option<&'a dyn Solver>,
+    /// Options of every restart, limits included.
+    pub solver_options: SolverOptions,
+}
+
+/// A shortened solution, and how many moves each technique saved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Improvement {
+    pub moves: Vec<u8>,
+    pub dropped: usize,
+    pub replaced: usize,
+    pub restarted: usize,
+}
+
+fn floods(grid: &Grid, moves: &[u8]) -> bool {
+    grid.clone().apply_solution(moves)
+}
+
+/// States after every prefix of `moves`, the initial one first.
+fn states(graph: &RegionGraph, moves: &[u8]) -> Vec<FloodState> {
+    let mut states = vec![graph.initial_state()];
+    for &color in moves {
+        states.push(graph.play(states.last().unwrap(), color));
+    }
+    states
+}
+
+/// Shortens `moves`, a solution of `grid`, until a full round of the three
+/// techniques saves nothing or the budget runs out. `on_improve` sees every
+/// shorter solution.
+pub fn improve(grid: &Grid, moves: &[u8], options: &ImproveOptions, budget: &Budget, on_improve: &mut dyn FnMut(&[u8])) -> Improvement {
+    assert!(floods(grid, moves), "improve needs a solution of the grid");
+    let graph = RegionGraph::new(grid);
+    let mut result = Improvement { moves: moves.to_vec(), dropped: 0, replaced: 0, restarted: 0 };
+    loop {
+        let before = result.moves.len();
+        drop_redundant(grid, &mut result, on_improve);
+        replace_windows(grid, &graph, options.window, &mut result, budget, on_improve);
+        if let Some(solver) = options.solver {
+            restart(grid, solver, &options.solver_options, &mut result, budget, on_improve);
+        }
+        if result.moves.len() == before || budget.exhausted() {
+            return result;
+        }
+    }
+}
+
+/// Removes every move the solution still works without, latest first.
+fn drop_redundant(grid: &Grid, result: &mut Improvement, on_improve: &mut dyn FnMut(&[u8])) {
+    for i in (0..result.moves.len()).rev() {
+        let mut candidate = result.moves.clone();
+        candidate.remove(i);
+        if floods(grid, &candidate) {
+            result.moves = candidate;
+            result.dropped += 1;
+            on_improve(&result.moves);
+        }
+    }
+}
+
+/// Replaces each run of `window` moves with the shortest sequence flooding at
+/// least the same components. Flooding is monotone, so the rest of the
+/// solution still works from a larger region.
+fn replace_windows(grid: &Grid, graph: &RegionGraph, window: usize, result: &mut Improvement, budget: &Budget, on_improve: &mut dyn FnMut(&[u8])) {
+    let mut i = 0;
+    while i < result.moves.len() && !budget.exhausted() {
+        let end = (i + window).min(result.moves.len());
+        let states = states(graph, &result.moves);
+        let complete = end == result.moves.len();
+        let bridge = (0..end - i).find_map(|depth| {
+            let mut line = Vec::new();
+            bridge(graph, &states[i], &states[end], complete, depth, &mut line, budget).then_some(line)
+        });
+        match bridge {
+            Some(line) => {
+                let candidate = [&result.moves[..i], &line, &result.moves[end..]].concat();
+                if floods(grid, &candidate) {
+                    result.replaced += result.moves.len() - candidate.len();
+                    result.moves = candidate;
+                    on_improve(&result.moves);
+                    continue;
+                }
+                i += 1;
+            }
+            None => i += 1,
+        }
+    }
+}
+
+/// Depth-limited search for `depth` moves from `state` flooding every
+/// component `target` floods, and finishing the grid if `complete`.
+fn bridge(graph: &RegionGraph, state: &FloodState, target: &FloodState, complete: bool, depth: usize, line: &mut Vec<u8>, budget: &Budget) -> bool {
+    if budget.expand() {
+        return false;
+    }
+    let mut missing = vec![false; graph.colors];
+    for c in target.absorbed.iter().filter(|&c| !state.absorbed.contains(c)) {
+        missing[graph.component_colors[c] as usize] = true;
+    }
+    let missing = missing.into_iter().filter(|&m| m).count();
+    if missing == 0 && (!complete || graph.is_complete(state)) {
+        return true;
+    }
+    if missing > depth || depth == 0 {
+        return false;
+    }
+    for color in graph.frontier_colors(state) {
+        line.push(color);
+        if bridge(graph, &graph.play(state, color), target, complete, depth - 1, line, budget) {
+            return true;
+        }
+        line.pop();
+    }
+    false
+}
+
+/// Runs `solver` on the grid left by every prefix of the solution, longest
+/// prefix first, and keeps the first shorter completion. Earlier states are
+/// harder, so it stops at the first restart cut short by its limits.
+fn restart(grid: &Grid, solver: &dyn Solver, options: &SolverOptions, result: &mut Improvement, budget: &Budget, on_improve: &mut dyn FnMut(&[u8])) {
+    let mut p = result.moves.len().saturating_sub(1);
+    while p > 0 && !budget.exhausted() {
+        let mut intermediate = grid.clone();
+        intermediate.apply_solution(&result.moves[..p]);
+        let solution = solver.solve(&intermediate, options, &mut |_| {});
+        let candidate = [&result.moves[..p], &solution.moves].concat();
+        if candidate.len() < result.moves.len() && floods(grid, &candidate) {
+            result.restarted += result.moves.len() - candidate.len();
+            result.moves = candidate;
+            on_improve(&result.moves);
+        }
+        if solution.stopped.is_some() {
+            return;
+        }
+        p -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{generate, Pattern};
+    use crate::solver::Registry;
+
+    const SAMPLE: &str = "1,2,0,0\n0,1,1,0\n2,2,0,1\n0,0,0,1";
+
+    fn options(solver: Option<&dyn Solver>, window: usize) -> ImproveOptions<'_> {
+        ImproveOptions { window, solver, solver_options: SolverOptions::default() }
+    }
+
+    #[test]
+    fn test_drops_redundant_moves() {
+        let grid = Grid::from_csv(SAMPLE).unwrap();
+        // Replaying the colour the region already has changes nothing
+        let moves = [0, 0, 2, 2, 0, 1, 1];
+        let result = improve(&grid, &moves, &options(None, 0), &Budget::unlimited(), &mut |_| {});
+        assert_eq!(result.moves, vec![0, 2, 0, 1]);
+        assert_eq!(result.dropped, 3);
+    }
+
+    #[test]
+    fn test_windows_reach_the_optimum_on_small_grids() {
+        let registry = Registry::default();
+        let astar = registry.get("astar").unwrap();
+        for seed in 0..4 {
+            let grid = generate(6, 6, 4, Pattern::Uniform, seed);
+            let optimal = astar.solve(&grid, &SolverOptions::default(), &mut |_| {}).moves;
+            let greedy = registry.get("greedy").unwrap().solve(&grid, &SolverOptions::default(), &mut |_| {}).moves;
+            let mut seen = Vec::new();
+            let result = improve(&grid, &greedy, &options(None, greedy.len()), &Budget::unlimited(), &mut |moves| seen.push(moves.len()));
+            assert_eq!(result.moves.len(), optimal.len(), "seed {}", seed);
+            assert_eq!(greedy.len() - result.moves.len(), result.dropped + result.replaced);
+            assert!(seen.windows(2).all(|w| w[0] > w[1]));
+            assert!(grid.is_solution(&result.moves));
+        }
+    }
+
+    #[test]
+    fn test_restarts_finish_with_the_stronger_solver() {
+        let registry = Registry::default();
+        let grid = generate(8, 8, 5, Pattern::Uniform, 7);
+        let optimal = registry.get("astar").unwrap().solve(&grid, &SolverOptions::default(), &mut |_| {}).moves;
+        let greedy = registry.get("greedy").unwrap().solve(&grid, &SolverOptions::default(), &mut |_| {}).moves;
+        let result = improve(&grid, &greedy, &options(registry.get("astar"), 0), &Budget::unlimited(), &mut |_| {});
+        assert!(result.restarted > 0);
+        // The first move is kept, and costs at most one move over optimal
+        assert!(result.moves.len() <= optimal.len() + 1);
+        assert!(grid.is_solution(&result.moves));
+    }
+}
@@ -0,0 +1,71 @@
+//! Solvers for the Flood-It puzzle: starting from the top-left cell, repeatedly
+//! recolour the flooded region until the whole grid has a single colour, using
+//! as few moves as possible.
+//!
+//! Load a grid from CSV, pick a strategy from the [`Registry`] and check the
+//! result against the pixel-level [`Grid::flood_fill`]:
+//!
+//! ```
+//! use color_it::{Grid, Registry, SolverOptions};
+//!
+//! let grid = Grid::from_csv("1,2,0,0\n0,1,1,0\n2,2,0,1\n0,0,0,1").unwrap();
+//! let registry = Registry::default();
+//! let solver = registry.get("astar").unwrap();
+//! let solution = solver.solve(&grid, &SolverOptions::default(), &mut |_| {});
+//!
+//! assert!(solution.proven_optimal);
+//! assert_eq!(solution.moves.len(), 4);
+//! assert!(grid.is_solution(&solution.moves));
+//! ```
+//!
+//! Solutions are saved one move per line:
+//!
+//! ```
+//! use color_it::{solution, Grid};
+//!
+//! let grid = Grid::from_csv("0,1\n1,1").unwrap();
+//! let moves = solution::parse("1\n").unwrap();
+//! assert_eq!(solution::to_string(&moves), "1\n");
+//! assert!(grid.is_solution(&moves));
+//! ```
+//!
+//! Custom strategies implement [`Solver`], or wrap a function in [`FnSolver`],
+//! and are added with [`Registry::register`].
+
+pub mod adversarial;
+pub mod astar;
+pub mod bench;
+pub mod bound;
+pub mod budget;
+pub mod dfs;
+pub mod duel;
+pub mod error;
+pub mod export;
+pub mod flood;
+pub mod free;
+pub mod generate;
+pub mod greedy;
+pub mod grid;
+pub mod import;
+pub mod improve;
+pub mod montecarlo;
+pub mod parallel;
+pub mod region;
+pub mod render;
+pub mod rng;
+pub mod sat;
+pub mod session;
+pub mod solution;
+pub mod solver;
+pub mod table;
+pub mod topology;
+pub mod verify;
+
+pub use bound::Bounds;
+pub use budget::{Budget, Limits, StopReason};
+pub use error::ParseError;
+pub use flood::Flood;
+pub use grid::Grid;
+pub use region::{FloodState, RegionGraph};
+pub use solver::{FnSolver, Progress, Registry, Solution, Solver, SolverOptions};
+pub use topology::Topology;
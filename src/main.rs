@@ -1,354 +1,1302 @@
-use nalgebra::{DMatrix};
-use std::collections::{HashSet};
 use std::error::Error;
-use clap::{Arg, ArgAction, Command};
+use std::io::{BufRead, IsTerminal, Write};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use clap::builder::PossibleValuesParser;
+use clap::{Arg, ArgAction, ArgGroup, ArgMatches, Command};
 
-#[derive(Debug, Clone)]
-struct Grid {
-    width: usize,
-    height: usize,
-    colors: usize,
-    data: DMatrix<u8>,  // 2D matrix to represent the grid
-}
+use color_it::adversarial;
+use color_it::bench::{self, Case};
+use color_it::bound::Gap;
+use color_it::duel::{self, Agent, Duel};
+use color_it::export::{self, Style};
+use color_it::free;
+use color_it::greedy::{self, GreedyScore};
+use color_it::generate::{self, Pattern};
+use color_it::import::{self, ImportOptions};
+use color_it::improve::{self, ImproveOptions};
+use color_it::montecarlo::{MonteCarloOptions, Rollout};
+use color_it::render;
+use color_it::sat::{self, Encoding, Outcome, SatSolver, SearchOrder};
+use color_it::session::Session;
+use color_it::solution;
+use color_it::verify::{self, Verdict};
+use color_it::table::DEFAULT_TABLE_BYTES;
+use color_it::{Bounds, Budget, Flood, Grid, Limits, Registry, SolverOptions, Topology};
 
-impl Grid {
-    fn new(width: usize, height: usize, colors: usize) -> Self {
-        Grid {
-            width,
-            height,
-            colors,
-            data: DMatrix::from_element(height, width, 0),  // Initialize with default color (0)
-        }
+fn save_solution(moves: &[u8], output_file: Option<&str>) -> Result<(), Box<dyn Error>> {
+    if let Some(file) = output_file {
+        std::fs::write(file, solution::to_string(moves))?;
+    } else {
+        println!("Solution: {:?}", moves);
     }
 
-    fn apply_solution(&mut self, solution: &[u8]) -> bool {
-        for &color in solution {
-            self.flood_fill(color);
-        }
-        self.is_complete()
-    }
+    Ok(())
+}
 
-    fn print_stats(&self) {
-        println!("Grid Statistics:");
-        println!("  Size: {}x{}", self.width, self.height);
-        println!("  Cells: {}", self.width * self.height);
-        println!("  Colors: {}", self.colors);
-        println!("  Search Space: {}", (self.colors as f64).powf((self.width * self.height) as f64));
-    }
+#[cfg(feature = "peak-memory")]
+#[global_allocator]
+static ALLOCATOR: bench::PeakAlloc = bench::PeakAlloc;
 
-    fn from_csv(content: &str) -> Option<Self> {
-        let rows: Vec<&str> = content.trim().split('\n').collect();
-        let height = rows.len();
-        let width = rows.get(0)?.split(',').count(); // Get the number of columns from the first row
+/// The grid in colour on a terminal, as plain text otherwise.
+fn show(grid: &Grid) -> String {
+    if std::io::stdout().is_terminal() {
+        render::ansi(grid)
+    } else {
+        grid.to_string()
+    }
+}
 
-        let mut data = DMatrix::zeros(height, width);
-        let mut colors = 0;
+fn load_grid(file: &str, square: bool) -> Result<Grid, Box<dyn Error>> {
+    let input = std::fs::read_to_string(file).map_err(|err| format!("cannot read {}: {}", file, err))?;
+    let grid = if square { Grid::from_square_csv(&input) } else { Grid::from_csv(&input) };
+    Ok(grid.map_err(|err| format!("{}: {}", file, err))?)
+}
 
-        for (i, row) in rows.iter().enumerate() {
-            for (j, val) in row.split(',').enumerate() {
-                let color = val.trim().parse::<u8>().ok()?;
-                colors = colors.max(color as usize + 1);
-                data[(i, j)] = color;
-            }
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
         }
-
-        Some(Grid { width, height, colors, data })
     }
+}
 
-    fn to_csv(&self) -> String {
-        let mut result = String::new();
-        for i in 0..self.height {
-            for j in 0..self.width {
-                if j > 0 {
-                    result.push(',');
-                }
-                result.push_str(&self.data[(i, j)].to_string());
-            }
-            result.push('\n');
-        }
-        result
-    }
+fn run() -> Result<ExitCode, Box<dyn Error>> {
+    let registry = Registry::default();
+    let matches = Command::new("Grid Coloring Solver")
+        .version("1.0")
+        .author("Laurent Valdes <valderama@gmail.com>")
+        .about("Solves a grid coloring problem")
+        .arg(
+            Arg::new("input")
+                .short('i')
+                .default_value("small-grid.csv")
+                .long("input")
+                .value_name("FILE")
+                .help("Input CSV file"),
+        )
+        .arg(
+            Arg::new("square")
+                .long("square")
+                .action(ArgAction::SetTrue)
+                .help("Reject input grids that are not square"),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .default_value("output.csv")
+                .long("output")
+                .value_name("FILE")
+                .help("Output file for the solution"),
+        )
+        .arg(
+            Arg::new("strategy")
+                .short('s')
+                .long("strategy")
+                .default_value("dfs")
+                .value_parser(PossibleValuesParser::new(registry.names()))
+                .help("Search strategy, see --list-strategies"),
+        )
+        .arg(
+            Arg::new("list-strategies")
+                .long("list-strategies")
+                .action(ArgAction::SetTrue)
+                .help("List the registered strategies and exit"),
+        )
+        .arg(
+            Arg::new("lookahead")
+                .short('k')
+                .long("lookahead")
+                .default_value("2")
+                .value_parser(clap::value_parser!(usize))
+                .help("Depth of the lookahead strategy"),
+        )
+        .arg(
+            Arg::new("beam-width")
+                .short('w')
+                .long("beam-width")
+                .default_value("16")
+                .value_parser(clap::value_parser!(usize))
+                .help("Number of states kept per depth by the beam strategy"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .default_value("0")
+                .value_parser(clap::value_parser!(u64))
+                .help("Seed of the mcts, nmcs and nrpa strategies"),
+        )
+        .arg(
+            Arg::new("iterations")
+                .long("iterations")
+                .value_parser(clap::value_parser!(usize))
+                .help("Playouts of each mcts search, or iterations per nrpa level (10000 and 100 by default)"),
+        )
+        .arg(
+            Arg::new("level")
+                .long("level")
+                .default_value("2")
+                .value_parser(clap::value_parser!(usize))
+                .help("Nesting level of the nmcs and nrpa strategies"),
+        )
+        .arg(
+            Arg::new("roots")
+                .long("roots")
+                .default_value("1")
+                .value_parser(clap::value_parser!(usize))
+                .help("Independent mcts, nmcs or nrpa searches run in parallel"),
+        )
+        .arg(
+            Arg::new("rollout")
+                .long("rollout")
+                .default_value("greedy")
+                .value_parser(PossibleValuesParser::new(Rollout::NAMES))
+                .help("Playouts of the Monte Carlo strategies: random, or greedy-biased"),
+        )
+        .arg(
+            Arg::new("table-size")
+                .long("table-size")
+                .value_parser(clap::value_parser!(usize))
+                .value_name("MiB")
+                .help("Memory cap of the transposition table (defaults to 64 MiB)"),
+        )
+        .arg(
+            Arg::new("time-limit")
+                .long("time-limit")
+                .value_parser(clap::value_parser!(f64))
+                .value_name("SECONDS")
+                .help("Stop searching after this long and keep the best solution found"),
+        )
+        .arg(
+            Arg::new("node-limit")
+                .long("node-limit")
+                .value_parser(clap::value_parser!(usize))
+                .help("Stop searching after expanding this many nodes"),
+        )
+        .arg(
+            Arg::new("memory-limit")
+                .long("memory-limit")
+                .value_parser(clap::value_parser!(usize))
+                .value_name("MiB")
+                .help("Stop searching once the search uses about this much memory"),
+        )
+        .arg(
+            Arg::new("threads")
+                .short('t')
+                .long("threads")
+                .global(true)
+                .value_parser(clap::value_parser!(usize))
+                .help("Size of the worker pool (defaults to one thread per core)"),
+        )
+        .arg(
+            Arg::new("output-grids")
+                .short('g')
+                .long("output-grids")
+                .action(ArgAction::SetTrue)
+                .help("Output the grids to the console"),
+        )
+        .arg(
+            Arg::new("render")
+                .long("render")
+                .value_name("FILE")
+                .help("Animate the solution into a .gif or .png (APNG) file instead of printing the grids"),
+        )
+        .args(render_args())
+        .args_conflicts_with_subcommands(true)
+        .subcommand(
+            Command::new("verify")
+                .about("Replays a solution file on a grid and checks that it floods it")
+                .after_help(
+                    "Solution files with row,column,colour lines are replayed as Free-Flood-It moves. \
+                     Exit status: 0 if the solution is valid, 2 if it plays a colour or a cell the grid does not have, \
+                     or a Free-Flood-It move once the board is one colour, 3 if it leaves cells unflooded, 1 on other errors.",
+                )
+                .arg(Arg::new("grid").required(true).value_name("GRID").help("Grid CSV file"))
+                .arg(Arg::new("solution").required(true).value_name("SOLUTION").help("Solution file, one colour or one row,column,colour move per line"))
+                .arg(
+                    Arg::new("steps")
+                        .long("steps")
+                        .action(ArgAction::SetTrue)
+                        .help("Print the flooded region size after every move"),
+                )
+                .arg(
+                    Arg::new("output-grids")
+                        .short('g')
+                        .long("output-grids")
+                        .action(ArgAction::SetTrue)
+                        .help("Print the grid after every move"),
+                ),
+        )
+        .subcommand(
+            Command::new("bound")
+                .about("Computes lower bounds on the moves a grid needs, and how far a solution can be from optimal")
+                .arg(Arg::new("grid").required(true).value_name("GRID").help("Grid CSV file"))
+                .arg(Arg::new("solution").value_name("SOLUTION").help("Solution file to compare with the bound")),
+        )
+        .subcommand(
+            Command::new("free")
+                .about("Solves the Free-Flood-It variant, where every move also picks the cell whose region it recolours")
+                .arg(Arg::new("grid").required(true).value_name("GRID").help("Grid CSV file"))
+                .arg(
+                    Arg::new("strategy")
+                        .short('s')
+                        .long("strategy")
+                        .default_value("greedy")
+                        .value_parser(PossibleValuesParser::new(free::SOLVER_NAMES))
+                        .help("greedy, or exact for small boards"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Solution file, one row,column,colour move per line"),
+                )
+                .arg(
+                    Arg::new("time-limit")
+                        .long("time-limit")
+                        .value_parser(clap::value_parser!(f64))
+                        .value_name("SECONDS")
+                        .help("Stop the exact search after this many seconds, keeping the best solution found"),
+                )
+                .arg(
+                    Arg::new("node-limit")
+                        .long("node-limit")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Stop the exact search after expanding this many nodes"),
+                )
+                .arg(
+                    Arg::new("memory-limit")
+                        .long("memory-limit")
+                        .value_parser(clap::value_parser!(usize))
+                        .value_name("MiB")
+                        .help("Stop the exact search once it uses about this much memory"),
+                ),
+        )
+        .subcommand(
+            Command::new("sat")
+                .about("Encodes a grid for external SAT or ILP solvers, decodes their answers, or finds an optimal solution with a SAT solver")
+                .after_help(
+                    "With --solver, the solver is asked whether the grid can be flooded in k moves for k between the lower bound \
+                     and the best solution found so far. In --solver-arg, {input} stands for the CNF file and {output} for a result \
+                     file the solver writes; by default the CNF file is passed last and the answer read from standard output.",
+                )
+                .arg(Arg::new("grid").required(true).value_name("GRID").help("Grid CSV file"))
+                .arg(
+                    Arg::new("steps")
+                        .short('k')
+                        .long("steps")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Largest number of moves to encode (by default, the greedy solution's length)"),
+                )
+                .arg(Arg::new("dimacs").long("dimacs").value_name("FILE").help("Write the DIMACS CNF encoding"))
+                .arg(Arg::new("lp").long("lp").value_name("FILE").help("Write the CPLEX LP encoding, minimising the moves played"))
+                .arg(
+                    Arg::new("assignment")
+                        .long("assignment")
+                        .value_name("FILE")
+                        .requires("steps")
+                        .conflicts_with("solver")
+                        .help("Decode a SAT solver's answer to the encoding for -k moves"),
+                )
+                .arg(Arg::new("solver").long("solver").value_name("PROGRAM").help("SAT solver binary to search for an optimal solution with"))
+                .arg(
+                    Arg::new("solver-arg")
+                        .long("solver-arg")
+                        .value_name("ARG")
+                        .action(ArgAction::Append)
+                        .allow_hyphen_values(true)
+                        .requires("solver")
+                        .help("Argument of the solver, repeatable"),
+                )
+                .arg(
+                    Arg::new("search")
+                        .long("search")
+                        .default_value("binary")
+                        .value_parser(PossibleValuesParser::new(sat::SearchOrder::NAMES))
+                        .help("binary halves the range of k, linear counts up from the lower bound"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Solution file, one colour per line"),
+                )
+                .arg(
+                    Arg::new("time-limit")
+                        .long("time-limit")
+                        .value_parser(clap::value_parser!(f64))
+                        .value_name("SECONDS")
+                        .help("Stop the solver after this many seconds, keeping the best solution found"),
+                )
+                .group(ArgGroup::new("action").args(["dimacs", "lp", "assignment", "solver"]).multiple(true).required(true)),
+        )
+        .subcommand(
+            Command::new("improve")
+                .about("Shortens an existing solution by local search")
+                .after_help(
+                    "Drops moves the solution works without, replaces every run of --window moves with the shortest \
+                     sequence flooding as much, and restarts --strategy from the states the solution goes through, \
+                     latest first, until a restart reaches --restart-time.",
+                )
+                .arg(Arg::new("grid").required(true).value_name("GRID").help("Grid CSV file"))
+                .arg(Arg::new("solution").required(true).value_name("SOLUTION").help("Solution file, one colour per line"))
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Shortened solution file, one colour per line"),
+                )
+                .arg(
+                    Arg::new("window")
+                        .long("window")
+                        .default_value("5")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Longest run of moves replaced by an exact search"),
+                )
+                .arg(
+                    Arg::new("strategy")
+                        .short('s')
+                        .long("strategy")
+                        .default_value("astar")
+                        .value_parser(PossibleValuesParser::new(registry.names()))
+                        .help("Solver restarted from intermediate states"),
+                )
+                .arg(
+                    Arg::new("no-restart")
+                        .long("no-restart")
+                        .action(ArgAction::SetTrue)
+                        .help("Only drop moves and replace runs of moves"),
+                )
+                .arg(
+                    Arg::new("restart-time")
+                        .long("restart-time")
+                        .default_value("10")
+                        .value_parser(clap::value_parser!(f64))
+                        .value_name("SECONDS")
+                        .help("Time limit of each restart"),
+                )
+                .arg(
+                    Arg::new("time-limit")
+                        .long("time-limit")
+                        .value_parser(clap::value_parser!(f64))
+                        .value_name("SECONDS")
+                        .help("Stop after this many seconds, keeping the shortest solution found"),
+                )
+                .arg(
+                    Arg::new("node-limit")
+                        .long("node-limit")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Stop after expanding this many nodes in the exact searches"),
+                )
+                .arg(
+                    Arg::new("memory-limit")
+                        .long("memory-limit")
+                        .value_parser(clap::value_parser!(usize))
+                        .value_name("MiB")
+                        .help("Memory limit of each restart"),
+                ),
+        )
+        .subcommand(
+            Command::new("render")
+                .about("Draws a grid as a PNG, or the playback of a solution as an animated GIF or PNG")
+                .arg(Arg::new("grid").required(true).value_name("GRID").help("Grid CSV file"))
+                .arg(Arg::new("solution").value_name("SOLUTION").help("Solution file, one colour per line"))
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .required(true)
+                        .value_name("FILE")
+                        .help("Image file; .gif animates as GIF, anything else is written as PNG"),
+                )
+                .args(render_args()),
+        )
+        .subcommand(
+            Command::new("import-image")
+                .about("Reads a grid from a PNG picture of a board cropped to the board")
+                .after_help("Exit status: 0 on success, 2 if some cells are far from every colour of the palette (the grid is written anyway), 1 on other errors.")
+                .arg(Arg::new("image").required(true).value_name("IMAGE").help("PNG file"))
+                .arg(
+                    Arg::new("width")
+                        .long("width")
+                        .requires("height")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Grid width in cells (detected from the picture by default)"),
+                )
+                .arg(
+                    Arg::new("height")
+                        .long("height")
+                        .requires("width")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Grid height in cells"),
+                )
+                .arg(
+                    Arg::new("colors")
+                        .short('c')
+                        .long("colors")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Number of colours (by default, as many as the tolerance calls for)"),
+                )
+                .arg(
+                    Arg::new("tolerance")
+                        .long("tolerance")
+                        .default_value("40")
+                        .value_parser(clap::value_parser!(f64))
+                        .help("Largest RGB distance between a cell and its palette colour"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Output CSV file (defaults to standard output)"),
+                )
+                .arg(
+                    Arg::new("palette-output")
+                        .long("palette-output")
+                        .value_name("FILE")
+                        .help("Writes the RGB colour of every colour index, one \"index,#rrggbb\" line each"),
+                ),
+        )
+        .subcommand(
+            Command::new("generate")
+                .about("Writes a random or adversarial grid as CSV")
+                .args(pattern_args())
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .value_parser(clap::value_parser!(u64))
+                        .help("Random seed (defaults to one derived from the clock, printed on stderr)"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Output CSV file (defaults to standard output)"),
+                ),
+        )
+        .subcommand(
+            Command::new("bench")
+                .about("Runs strategies over a suite of grids and compares their solutions")
+                .arg(
+                    Arg::new("files")
+                        .value_name("GRID")
+                        .num_args(0..)
+                        .help("Grid CSV files to include in the suite"),
+                )
+                .arg(
+                    Arg::new("generate")
+                        .short('n')
+                        .long("generate")
+                        .value_parser(clap::value_parser!(u64))
+                        .help("Number of generated grids to include (defaults to 5 when no file is given)"),
+                )
+                .args(pattern_args())
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .default_value("0")
+                        .value_parser(clap::value_parser!(u64))
+                        .help("Seed of the first generated grid; the others follow"),
+                )
+                .arg(
+                    Arg::new("strategies")
+                        .short('s')
+                        .long("strategies")
+                        .value_delimiter(',')
+                        .value_parser(PossibleValuesParser::new(registry.names()))
+                        .help("Comma-separated strategies to run (defaults to all)"),
+                )
+                .arg(
+                    Arg::new("time-limit")
+                        .long("time-limit")
+                        .default_value("10")
+                        .value_parser(clap::value_parser!(f64))
+                        .value_name("SECONDS")
+                        .help("Time limit of each run"),
+                )
+                .arg(
+                    Arg::new("node-limit")
+                        .long("node-limit")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Node limit of each run"),
+                )
+                .arg(
+                    Arg::new("memory-limit")
+                        .long("memory-limit")
+                        .value_parser(clap::value_parser!(usize))
+                        .value_name("MiB")
+                        .help("Memory limit of each run"),
+                )
+                .arg(Arg::new("csv").long("csv").value_name("FILE").help("Write the records as CSV"))
+                .arg(Arg::new("json").long("json").value_name("FILE").help("Write the records as JSON")),
+        )
+        .subcommand(
+            Command::new("play")
+                .about("Plays a grid by hand on the terminal")
+                .after_help(
+                    "Type a colour to play it, u to undo, r to redo, h for a hint, s to save the moves so far and q to quit.",
+                )
+                .arg(Arg::new("grid").required(true).value_name("GRID").help("Grid CSV file"))
+                .arg(
+                    Arg::new("strategy")
+                        .short('s')
+                        .long("strategy")
+                        .default_value("beam")
+                        .value_parser(PossibleValuesParser::new(registry.names()))
+                        .help("Strategy giving hints and the best known move count"),
+                )
+                .arg(
+                    Arg::new("time-limit")
+                        .long("time-limit")
+                        .default_value("2")
+                        .value_parser(clap::value_parser!(f64))
+                        .value_name("SECONDS")
+                        .help("Time limit of each call to the strategy"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .default_value("output.csv")
+                        .value_name("FILE")
+                        .help("Solution file written by s and on completion"),
+                ),
+        )
+        .subcommand(
+            Command::new("duel")
+                .about("Plays two-player Flood-It between engines or against the computer")
+                .after_help(
+                    "Engines: human, greedy, random, minimax[:DEPTH] (4 by default), mcts[:ITERATIONS] (1000 by default). \
+                     With a human player a single game is played on the terminal; otherwise the engines play a match, \
+                     two games per grid with the seats swapped, and the win rates are reported.",
+                )
+                .arg(Arg::new("grid").value_name("GRID").help("Grid CSV file (defaults to generated grids)"))
+                .arg(Arg::new("first").long("first").default_value("human").help("Engine moving first"))
+                .arg(Arg::new("second").long("second").default_value("mcts").help("Engine moving second"))
+                .arg(
+                    Arg::new("games")
+                        .short('n')
+                        .long("games")
+                        .default_value("5")
+                        .value_parser(clap::value_parser!(u64))
+                        .help("Number of generated grids a match is played on"),
+                )
+                .args(pattern_args())
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .default_value("0")
+                        .value_parser(clap::value_parser!(u64))
+                        .help("Seed of the first generated grid and of the engines"),
+                ),
+        )
+        .get_matches();
 
-    fn flood_fill(&mut self, target_color: u8) {
-        // Ensure grid dimensions are valid
-        assert!(self.width > 0, "Width must be greater than zero");
-        assert!(self.height > 0, "Height must be greater than zero");
+    if let Some(&threads) = matches.get_one::<usize>("threads") {
+        rayon::ThreadPoolBuilder::new().num_threads(threads).build_global()?;
+    }
 
-        let source_color = self.data[(0, 0)]; // Starting color is the color at position (0, 0)
+    match matches.subcommand() {
+        Some(("verify", matches)) => return run_verify(matches),
+        Some(("bound", matches)) => return run_bound(matches),
+        Some(("free", matches)) => return run_free(matches),
+        Some(("sat", matches)) => return run_sat(matches),
+        Some(("improve", matches)) => return run_improve(&registry, matches),
+        Some(("render", matches)) => return run_render(matches),
+        Some(("import-image", matches)) => return run_import_image(matches),
+        Some(("generate", matches)) => return run_generate(matches),
+        Some(("bench", matches)) => return run_bench(&registry, matches),
+        Some(("duel", matches)) => return run_duel(matches),
+        Some(("play", matches)) => return run_play(&registry, matches),
+        _ => {}
+    }
 
-        // If the source color is the same as the target color, no need to do anything
-        if source_color == target_color {
-            println!("Source color {} is the same as target color {}", source_color, target_color);
-            return;
+    if matches.get_flag("list-strategies") {
+        for solver in registry.iter() {
+            println!("{:<14} {}", solver.name(), solver.description());
         }
+        return Ok(ExitCode::SUCCESS);
+    }
 
-        // Use a stack to implement depth-first search (DFS)
-        let mut stack = Vec::new();
-        stack.push((0, 0)); // Start from the top-left corner
+    let input_file = matches.get_one::<String>("input").expect("required input file");
+    let output_file = matches.get_one::<String>("output");
+    let render_file = matches.get_one::<String>("render");
+    let output_grids = matches.get_flag("output-grids") && render_file.is_none();
+    let strategy = matches.get_one::<String>("strategy").expect("default strategy");
+    let options = SolverOptions {
+        table_bytes: matches.get_one::<usize>("table-size").map_or(DEFAULT_TABLE_BYTES, |&mib| mib << 20),
+        lookahead: *matches.get_one::<usize>("lookahead").expect("default lookahead"),
+        beam_width: *matches.get_one::<usize>("beam-width").expect("default beam width"),
+        monte_carlo: MonteCarloOptions {
+            seed: *matches.get_one::<u64>("seed").expect("default seed"),
+            iterations: matches.get_one::<usize>("iterations").copied(),
+            level: *matches.get_one::<usize>("level").expect("default level"),
+            roots: *matches.get_one::<usize>("roots").expect("default roots"),
+            rollout: Rollout::from_name(matches.get_one::<String>("rollout").expect("default rollout")).expect("rollout validated by clap"),
+        },
+        limits: limits_from(&matches)?,
+    };
 
-        // Create a visited set to avoid revisiting cells
-        let mut visited = vec![vec![false; self.width]; self.height];
+    let grid = load_grid(input_file, matches.get_flag("square"))?;
+    let solver = registry.get(strategy).expect("strategy validated by clap");
 
-        while let Some((x, y)) = stack.pop() {
-            // Ensure that the index is within the grid bounds
-            assert!(x < self.width && y < self.height, "Index out of bounds: ({}, {})", x, y);
+    grid.print_stats();
+    if output_grids {
+        println!("Initial grid:\n{}", show(&grid));
+    }
 
-            // Skip if already visited
-            if visited[y][x] {
-                continue;
+    let solution = solver.solve(&grid, &options, &mut |progress| {
+        println!("Found {} moves after {:.3?}", progress.moves.len(), progress.elapsed);
+        if output_grids {
+            let mut replay = Flood::new(&grid);
+            for &color in progress.moves {
+                replay.play(color);
+                println!("Applying move: {}, Current grid state:\n{}", color, show(&replay.to_grid()));
             }
+        }
+    });
 
-            // Check if the current cell has the source color
-            if self.data[(y, x)] == source_color {
-                // Fill the current cell with the target color
-                self.data[(y, x)] = target_color;
-                visited[y][x] = true; // Mark as visited
+    if output_grids {
+        println!("Final solution: {:?}", solution.moves);
+    }
 
-                // Left
-                if x > 0 {
-                    if !visited[y][x - 1] && self.data[(y, x - 1)] == source_color {
-                        stack.push((x - 1, y));
-                    }
-                }
+    assert!(grid.is_solution(&solution.moves), "solver returned an incomplete solution");
+    if solution.nodes_expanded > 0 {
+        println!("Nodes expanded: {}", solution.nodes_expanded);
+    }
+    if let Some(table) = solution.table {
+        println!("Transposition table: {}", table);
+    }
+    println!("Proven optimal: {}", if solution.proven_optimal { "yes" } else { "no" });
+    if let Some(reason) = solution.stopped {
+        println!("Stopped early: {}, keeping the best solution found", reason);
+    }
+    println!("Moves: {}", solution.moves.len());
+    println!("Time: {:.3?}", solution.elapsed);
+    save_solution(&solution.moves, output_file.map(|x| x.as_str()))?;
+    if let Some(file) = render_file {
+        write_render(file, &grid, &solution.moves, &matches)?;
+    }
 
-                // Right
-                if x < self.width - 1 {
-                    if !visited[y][x + 1] && self.data[(y, x + 1)] == source_color {
-                        stack.push((x + 1, y));
-                    }
-                }
+    Ok(ExitCode::SUCCESS)
+}
 
-                // Up
-                if y > 0 {
-                    if !visited[y - 1][x] && self.data[(y - 1, x)] == source_color {
-                        stack.push((x, y - 1));
-                    }
-                }
+fn run_verify(matches: &ArgMatches) -> Result<ExitCode, Box<dyn Error>> {
+    let grid_file = matches.get_one::<String>("grid").expect("required grid");
+    let solution_file = matches.get_one::<String>("solution").expect("required solution");
+    let output_grids = matches.get_flag("output-grids");
 
-                // Down
-                if y < self.height - 1 {
-                    if !visited[y + 1][x] && self.data[(y + 1, x)] == source_color {
-                        stack.push((x, y + 1));
-                    }
+    let grid = load_grid(grid_file, false)?;
+    let content = std::fs::read_to_string(solution_file)
+        .map_err(|err| format!("cannot read {}: {}", solution_file, err))?;
+    if solution::is_free(&content) {
+        let moves = solution::parse_free(&content)
+            .ok_or_else(|| format!("{}: expected one row,column,colour move per line", solution_file))?;
+        let report = verify::verify_free(&grid, &moves);
+        if matches.get_flag("steps") || output_grids {
+            let mut replay = grid.clone();
+            for (index, (step, m)) in report.steps.iter().zip(&moves).enumerate() {
+                println!("Move {}: row {}, column {}, colour {}, {} cells recoloured", index + 1, m.row, m.col, step.color, step.flooded);
+                if output_grids {
+                    replay.flood_fill_at(m.col, m.row, m.color);
+                    print!("{}", show(&replay));
                 }
             }
         }
+        print!("{}", report);
+        return Ok(verdict_code(report.verdict));
     }
 
-    fn is_complete(&self) -> bool {
-        let target = self.data[(0, 0)];
-        self.data.iter().all(|&color| color == target)
+    let moves = solution::parse(&content)
+        .ok_or_else(|| format!("{}: expected one colour from 0 to 255 per line", solution_file))?;
+    let report = verify::verify(&grid, &moves);
+    if matches.get_flag("steps") || output_grids {
+        let mut replay = Flood::new(&grid);
+        for (index, step) in report.steps.iter().enumerate() {
+            println!("Move {}: colour {}, {} cells flooded", index + 1, step.color, step.flooded);
+            if output_grids {
+                replay.play(step.color);
+                print!("{}", show(&replay.to_grid()));
+            }
+        }
     }
+    print!("{}", report);
+    Ok(verdict_code(report.verdict))
 }
 
-fn solve(grid: &mut Grid, output_grids: bool) -> Vec<u8> {
-    grid.print_stats();
-    if(output_grids) {
-        println!("Initial grid:\n{}", grid.data);
+fn verdict_code(verdict: Verdict) -> ExitCode {
+    match verdict {
+        Verdict::Valid => ExitCode::SUCCESS,
+        Verdict::Invalid => ExitCode::from(2),
+        Verdict::Incomplete => ExitCode::from(3),
     }
+}
 
-    if grid.is_complete() {
-        return Vec::new();
-    }
+fn run_bound(matches: &ArgMatches) -> Result<ExitCode, Box<dyn Error>> {
+    let grid = load_grid(matches.get_one::<String>("grid").expect("required grid"), false)?;
+    let bounds = Bounds::of_grid(&grid);
+    print!("{}", bounds);
 
-    struct SearchState {
-        moves: Vec<u8>,
-        grid_state: DMatrix<u8>, // Grid state as a matrix
+    if let Some(file) = matches.get_one::<String>("solution") {
+        let content = std::fs::read_to_string(file).map_err(|err| format!("cannot read {}: {}", file, err))?;
+        let moves = solution::parse(&content).ok_or_else(|| format!("{}: expected one colour from 0 to 255 per line", file))?;
+        let report = verify::verify(&grid, &moves);
+        if report.verdict != Verdict::Valid {
+            print!("{}", report);
+            return Ok(verdict_code(report.verdict));
+        }
+        println!("Solution: {}", Gap { bound: bounds.value(), moves: moves.len() });
     }
+    Ok(ExitCode::SUCCESS)
+}
 
-    let mut stack: Vec<SearchState> = Vec::new();
-    let mut visited: HashSet<DMatrix<u8>> = HashSet::new();
-    let mut best_solution = Vec::new();
-    let mut min_length = grid.width * grid.height;
+fn run_free(matches: &ArgMatches) -> Result<ExitCode, Box<dyn Error>> {
+    let grid = load_grid(matches.get_one::<String>("grid").expect("required grid"), false)?;
+    let strategy = matches.get_one::<String>("strategy").expect("default strategy");
+    let budget = Budget::new(&limits_from(matches)?);
+    let start = std::time::Instant::now();
+    let solution = free::solve(strategy, &grid, DEFAULT_TABLE_BYTES, &budget).expect("strategy validated by clap");
+
+    assert_eq!(verify::verify_free(&grid, &solution.moves).verdict, Verdict::Valid, "solver returned an invalid solution");
+    if solution.nodes_expanded > 0 {
+        println!("Nodes expanded: {}", solution.nodes_expanded);
+    }
+    println!("Proven optimal: {}", if solution.proven_optimal { "yes" } else { "no" });
+    if let Some(reason) = budget.stop_reason() {
+        println!("Stopped early: {}, keeping the best solution found", reason);
+    }
+    println!("Moves: {}", solution.moves.len());
+    println!("Time: {:.3?}", start.elapsed());
+    match matches.get_one::<String>("output") {
+        Some(file) => std::fs::write(file, solution::free_to_string(&solution.moves))?,
+        None => print!("{}", solution::free_to_string(&solution.moves)),
+    }
+    Ok(ExitCode::SUCCESS)
+}
 
-    // Initialize the stack with the first moves
-    for color in 0..grid.colors {
-        let color = color as u8;
-        if color != grid.data[(0, 0)] {
-            let mut temp_grid = grid.clone();
-            temp_grid.flood_fill(color);
+fn run_sat(matches: &ArgMatches) -> Result<ExitCode, Box<dyn Error>> {
+    let grid = load_grid(matches.get_one::<String>("grid").expect("required grid"), false)?;
+    let steps = match matches.get_one::<usize>("steps") {
+        Some(&steps) => steps,
+        None => greedy::solve_greedy(&grid, GreedyScore::Cells, &Budget::unlimited()).len(),
+    };
+    let encoding = Encoding::new(&grid, steps);
+    let create = |file: &str| {
+        std::fs::File::create(file).map(std::io::BufWriter::new).map_err(|err| format!("cannot create {}: {}", file, err))
+    };
+    if let Some(file) = matches.get_one::<String>("dimacs") {
+        encoding.write_dimacs(create(file)?)?;
+        println!("Wrote {}: {} variables, {} clauses for at most {} moves", file, encoding.variables(), encoding.clauses().len(), steps);
+    }
+    if let Some(file) = matches.get_one::<String>("lp") {
+        encoding.write_lp(create(file)?)?;
+        println!("Wrote {}: {} binary variables for at most {} moves", file, encoding.variables(), steps);
+    }
+    let output_file = matches.get_one::<String>("output").map(|file| file.as_str());
 
-            if visited.insert(temp_grid.data.clone()) {
-                stack.push(SearchState {
-                    moves: vec![color],
-                    grid_state: temp_grid.data.clone(),
-                });
+    if let Some(file) = matches.get_one::<String>("assignment") {
+        let content = std::fs::read_to_string(file).map_err(|err| format!("cannot read {}: {}", file, err))?;
+        match sat::parse_outcome(&content).map_err(|err| format!("{}: {}", file, err))? {
+            Outcome::Satisfiable(assignment) => {
+                let moves = encoding.decode(&grid, &assignment).map_err(|err| format!("{}: {}", file, err))?;
+                println!("Moves: {}", moves.len());
+                save_solution(&moves, output_file)?;
             }
+            Outcome::Unsatisfiable => println!("Unsatisfiable: no solution has at most {} moves", steps),
+            Outcome::Unknown => println!("Unknown: the solver gave up"),
         }
     }
 
-    // Perform the search
-    while let Some(state) = stack.pop() {
-        if state.moves.len() >= min_length {
-            continue;
+    if let Some(program) = matches.get_one::<String>("solver") {
+        let solver = SatSolver {
+            program: program.clone(),
+            args: matches.get_many::<String>("solver-arg").map_or_else(Vec::new, |args| args.cloned().collect()),
+        };
+        let order = SearchOrder::from_name(matches.get_one::<String>("search").expect("default search")).expect("search validated by clap");
+        let budget = Budget::new(&limits_from(matches)?);
+        let start = std::time::Instant::now();
+        let solution = sat::solve(&grid, &solver, order, &budget, &mut |call| {
+            let answer = match call.outcome {
+                Outcome::Satisfiable(_) => "satisfiable",
+                Outcome::Unsatisfiable => "unsatisfiable",
+                Outcome::Unknown => "unknown",
+            };
+            println!("At most {} moves: {} ({:.3?})", call.steps, answer, call.elapsed);
+        })?;
+
+        println!("Solver calls: {}", solution.calls);
+        println!("Proven optimal: {}", if solution.proven_optimal { "yes" } else { "no" });
+        if let Some(reason) = budget.stop_reason() {
+            println!("Stopped early: {}, keeping the best solution found", reason);
         }
+        println!("Moves: {}", solution.moves.len());
+        println!("Time: {:.3?}", start.elapsed());
+        save_solution(&solution.moves, output_file)?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
 
-        // Reconstruct the grid from the current state
-        let temp_grid = Grid {
-            width: grid.width,
-            height: grid.height,
-            colors: grid.colors,
-            data: state.grid_state.clone(),
-        };
+fn run_improve(registry: &Registry, matches: &ArgMatches) -> Result<ExitCode, Box<dyn Error>> {
+    let grid = load_grid(matches.get_one::<String>("grid").expect("required grid"), false)?;
+    let file = matches.get_one::<String>("solution").expect("required solution");
+    let content = std::fs::read_to_string(file).map_err(|err| format!("cannot read {}: {}", file, err))?;
+    let moves = solution::parse(&content).ok_or_else(|| format!("{}: expected one colour from 0 to 255 per line", file))?;
+    let report = verify::verify(&grid, &moves);
+    if report.verdict != Verdict::Valid {
+        print!("{}", report);
+        return Ok(verdict_code(report.verdict));
+    }
 
-        // Check if the grid is complete
-        if temp_grid.is_complete() {
-            if state.moves.len() < min_length {
-                best_solution = state.moves.clone();
-                min_length = state.moves.len();
+    let limits = limits_from(matches)?;
+    let restart_time = *matches.get_one::<f64>("restart-time").expect("default restart time");
+    let restart_time = Duration::try_from_secs_f64(restart_time).map_err(|_| format!("invalid restart time: {}", restart_time))?;
+    let strategy = matches.get_one::<String>("strategy").expect("default strategy");
+    let options = ImproveOptions {
+        window: *matches.get_one::<usize>("window").expect("default window"),
+        solver: (!matches.get_flag("no-restart")).then(|| registry.get(strategy).expect("strategy validated by clap")),
+        solver_options: SolverOptions {
+            limits: Limits { time: Some(restart_time), nodes: None, ..limits.clone() },
+            ..SolverOptions::default()
+        },
+    };
+    let budget = Budget::new(&Limits { memory: None, ..limits });
+    let start = std::time::Instant::now();
+    let result = improve::improve(&grid, &moves, &options, &budget, &mut |moves| {
+        println!("Found {} moves after {:.3?}", moves.len(), start.elapsed());
+    });
 
-                if output_grids {
-                    let mut original_grid = grid.clone();
+    assert!(grid.is_solution(&result.moves), "improve returned an incomplete solution");
+    println!("Dropped: {} moves", result.dropped);
+    println!("Replaced runs: {} moves", result.replaced);
+    println!("Restarts: {} moves", result.restarted);
+    if let Some(reason) = budget.stop_reason() {
+        println!("Stopped early: {}, keeping the shortest solution found", reason);
+    }
+    println!("Moves: {} (was {})", result.moves.len(), moves.len());
+    println!("Solution: {}", Gap { bound: Bounds::of_grid(&grid).value(), moves: result.moves.len() });
+    println!("Time: {:.3?}", start.elapsed());
+    save_solution(&result.moves, matches.get_one::<String>("output").map(|x| x.as_str()))?;
+    Ok(ExitCode::SUCCESS)
+}
 
-                    for &color in &best_solution {
-                        original_grid.flood_fill(color);
-                        if output_grids {
-                            println!("Applying move: {}, Current grid state:\n{}", color, original_grid.data);
-                        }
-                    }
-                }
-            }
-            continue;
+fn run_render(matches: &ArgMatches) -> Result<ExitCode, Box<dyn Error>> {
+    let grid = load_grid(matches.get_one::<String>("grid").expect("required grid"), false)?;
+    let moves = match matches.get_one::<String>("solution") {
+        Some(file) => {
+            let content = std::fs::read_to_string(file).map_err(|err| format!("cannot read {}: {}", file, err))?;
+            solution::parse(&content).ok_or_else(|| format!("{}: expected one colour from 0 to 255 per line", file))?
         }
+        None => Vec::new(),
+    };
+    write_render(matches.get_one::<String>("output").expect("required output"), &grid, &moves, matches)?;
+    Ok(ExitCode::SUCCESS)
+}
 
-        // Add next possible moves
-        for color in 0..grid.colors {
-            let color = color as u8;
+/// Options of the drawing shared by `--render` and the render subcommand.
+fn render_args() -> [Arg; 4] {
+    [
+        Arg::new("cell-size")
+            .long("cell-size")
+            .default_value("16")
+            .value_parser(clap::value_parser!(usize))
+            .value_name("PIXELS")
+            .help("Side of a cell in the rendered images"),
+        Arg::new("palette")
+            .long("palette")
+            .value_name("#RRGGBB,...")
+            .value_parser(export::parse_palette)
+            .help("Colours of the first colour indices in the rendered images"),
+        Arg::new("outline")
+            .long("outline")
+            .num_args(0..=1)
+            .default_missing_value("#ffffff")
+            .value_name("#RRGGBB")
+            .value_parser(|text: &str| export::parse_palette(text).map(|colors| colors[0]))
+            .help("Outline the flooded region in the rendered images"),
+        Arg::new("delay")
+            .long("delay")
+            .default_value("500")
+            .value_parser(clap::value_parser!(u16))
+            .value_name("MS")
+            .help("Time each move is shown in animations"),
+    ]
+}
 
-            // Skip moves that repeat the current color or backtrack
-            if color == temp_grid.data[(0, 0)] || (state.moves.last() == Some(&color)) {
-                continue;
-            }
+/// Writes the playback of `moves` on `grid` to `file`: a GIF when its
+/// extension is .gif, a PNG otherwise, animated unless there are no moves.
+fn write_render(file: &str, grid: &Grid, moves: &[u8], matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let style = Style {
+        cell_size: *matches.get_one::<usize>("cell-size").expect("default cell size"),
+        palette: matches.get_one::<Vec<[u8; 3]>>("palette").cloned().unwrap_or_default(),
+        outline: matches.get_one::<[u8; 3]>("outline").copied(),
+    };
+    let delay = *matches.get_one::<u16>("delay").expect("default delay");
+    let writer = std::io::BufWriter::new(std::fs::File::create(file).map_err(|err| format!("cannot create {}: {}", file, err))?);
+    let gif = std::path::Path::new(file).extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+    if gif {
+        export::write_gif(writer, &export::palette(grid, &style), export::frames(grid, moves, &style), delay)?;
+    } else if moves.is_empty() {
+        export::write_png(writer, &export::draw(grid, &|_, _| false, &style))?;
+    } else {
+        export::write_apng(writer, export::frames(grid, moves, &style), delay)?;
+    }
+    let frames = moves.len() + 1;
+    println!("Wrote {} frame{} to {}", frames, if frames == 1 { "" } else { "s" }, file);
+    Ok(())
+}
 
-            let mut next_grid = temp_grid.clone();
-            next_grid.flood_fill(color);
+/// Reads `--time-limit`, and `--node-limit` and `--memory-limit` where the
+/// subcommand has them, and makes Ctrl-C stop the search so the solver
+/// returns its best solution so far.
+fn limits_from(matches: &ArgMatches) -> Result<Limits, Box<dyn Error>> {
+    let time = match matches.get_one::<f64>("time-limit") {
+        Some(&seconds) => {
+            Some(Duration::try_from_secs_f64(seconds).map_err(|_| format!("invalid time limit: {}", seconds))?)
+        }
+        None => None,
+    };
 
-            // Only consider this move if it results in a new grid state
-            if visited.insert(next_grid.data.clone()) {
-                let mut next_moves = state.moves.clone();
-                next_moves.push(color);
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler_stop = stop.clone();
+    ctrlc::set_handler(move || handler_stop.store(true, Ordering::Relaxed))?;
 
-                if(output_grids) {
-                    // println!("Applying move: {}, Current grid state:\n{}", color, next_grid.data);
-                }
+    Ok(Limits {
+        time,
+        nodes: matches.try_get_one::<usize>("node-limit").ok().flatten().copied(),
+        memory: matches.try_get_one::<usize>("memory-limit").ok().flatten().map(|&mib| mib << 20),
+        stop: Some(stop),
+    })
+}
 
-                stack.push(SearchState {
-                    moves: next_moves,
-                    grid_state: next_grid.data.clone(),
-                });
-            }
-        }
-    }
+/// Arguments describing generated grids, shared by `generate`, `bench` and
+/// `duel`.
+fn pattern_args() -> [Arg; 7] {
+    [
+        Arg::new("width").long("width").default_value("14").value_parser(clap::value_parser!(usize)),
+        Arg::new("height").long("height").default_value("14").value_parser(clap::value_parser!(usize)),
+        Arg::new("colors").short('c').long("colors").default_value("6").value_parser(clap::value_parser!(usize)),
+        Arg::new("pattern")
+            .short('p')
+            .long("pattern")
+            .default_value("uniform")
+            .value_parser(PossibleValuesParser::new(Pattern::NAMES))
+            .help("Colour layout"),
+        Arg::new("patch-size")
+            .long("patch-size")
+            .default_value("8")
+            .value_parser(clap::value_parser!(usize))
+            .help("Average blob size of the clusters pattern, in cells"),
+        Arg::new("topology")
+            .long("topology")
+            .default_value("square")
+            .value_parser(PossibleValuesParser::new(Topology::NAMES))
+            .help("Which cells touch each other"),
+        Arg::new("start")
+            .long("start")
+            .value_name("X,Y[;X,Y...]")
+            .help("Cells the flood starts from, or \"center\" [default: 0,0]"),
+    ]
+}
 
-    if(output_grids) {
-        println!("Final solution: {:?}", best_solution);
+/// Grids described by the `pattern_args`.
+struct Layout {
+    width: usize,
+    height: usize,
+    colors: usize,
+    pattern: Pattern,
+    topology: Topology,
+    origins: Vec<(usize, usize)>,
+}
+
+impl Layout {
+    fn generate(&self, seed: u64) -> Grid {
+        let mut grid = generate::generate_on(self.topology, self.width, self.height, self.colors, self.pattern, seed);
+        grid.set_origins(&self.origins);
+        grid
     }
+}
 
-    best_solution
+fn pattern_from(matches: &ArgMatches) -> Result<Layout, Box<dyn Error>> {
+    let width = *matches.get_one::<usize>("width").expect("default width");
+    let height = *matches.get_one::<usize>("height").expect("default height");
+    let colors = *matches.get_one::<usize>("colors").expect("default colours");
+    if width == 0 || height == 0 {
+        return Err("width and height must be at least 1".into());
+    }
+    if !(1..=256).contains(&colors) {
+        return Err("the colour count must be between 1 and 256".into());
+    }
+    let pattern = match matches.get_one::<String>("pattern").expect("default pattern").parse()? {
+        Pattern::Clusters { .. } => {
+            Pattern::Clusters { patch_size: *matches.get_one::<usize>("patch-size").expect("default patch size") }
+        }
+        pattern => pattern,
+    };
+    let topology = matches.get_one::<String>("topology").expect("default topology").parse()?;
+    let origins = match matches.get_one::<String>("start").map(String::as_str) {
+        None => vec![(0, 0)],
+        Some("center") => vec![(width / 2, height / 2)],
+        Some(value) => Grid::parse_origins(value).map_err(|pair| format!("invalid start {:?}, expected X,Y", pair))?,
+    };
+    if let Some((x, y)) = origins.iter().find(|&&(x, y)| x >= width || y >= height) {
+        return Err(format!("start ({}, {}) is outside the {}x{} grid", x, y, width, height).into());
+    }
+    Ok(Layout { width, height, colors, pattern, topology, origins })
 }
 
-fn save_solution(moves: &[u8], output_file: Option<&str>) -> Result<(), Box<dyn Error>> {
-    let mut output = String::new();
-    for &m in moves {
-        output.push_str(&m.to_string());
-        output.push('\n');
+fn run_import_image(matches: &ArgMatches) -> Result<ExitCode, Box<dyn Error>> {
+    let file = matches.get_one::<String>("image").expect("required image");
+    let reader = std::io::BufReader::new(std::fs::File::open(file).map_err(|err| format!("cannot read {}: {}", file, err))?);
+    let image = import::decode_png(reader).map_err(|err| format!("{}: {}", file, err))?;
+    let options = ImportOptions {
+        dimensions: matches.get_one::<usize>("width").zip(matches.get_one::<usize>("height")).map(|(&w, &h)| (w, h)),
+        colors: matches.get_one::<usize>("colors").copied(),
+        tolerance: *matches.get_one::<f64>("tolerance").expect("default tolerance"),
+    };
+    let import = import::import(&image, &options).map_err(|err| format!("{}: {}", file, err))?;
+
+    let grid = &import.grid;
+    eprintln!("Grid: {}x{}, {} colours", grid.width(), grid.height(), grid.colors());
+    let palette: Vec<String> = import.palette.iter().map(|&rgb| export::hex(rgb)).collect();
+    eprintln!("Palette: {}", palette.join(","));
+    for cell in &import.ambiguous {
+        eprintln!(
+            "Ambiguous cell ({}, {}): {} is {:.0} away from colour {}",
+            cell.x,
+            cell.y,
+            export::hex(cell.rgb),
+            cell.distance,
+            grid.get(cell.x, cell.y)
+        );
     }
 
-    if let Some(file) = output_file {
-        std::fs::write(file, output)?;
-    } else {
-        println!("Solution: {:?}", moves);
+    match matches.get_one::<String>("output") {
+        Some(file) => std::fs::write(file, grid.to_csv())?,
+        None => print!("{}", grid.to_csv()),
+    }
+    if let Some(file) = matches.get_one::<String>("palette-output") {
+        let mapping: String = palette.iter().enumerate().map(|(color, hex)| format!("{},{}\n", color, hex)).collect();
+        std::fs::write(file, mapping)?;
     }
+    Ok(if import.ambiguous.is_empty() { ExitCode::SUCCESS } else { ExitCode::from(2) })
+}
 
-    Ok(())
+fn run_generate(matches: &ArgMatches) -> Result<ExitCode, Box<dyn Error>> {
+    let layout = pattern_from(matches)?;
+    let seed = match matches.get_one::<u64>("seed") {
+        Some(&seed) => seed,
+        None => {
+            let seed = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_nanos() as u64;
+            eprintln!("Seed: {}", seed);
+            seed
+        }
+    };
+
+    let csv = layout.generate(seed).to_csv();
+    match matches.get_one::<String>("output") {
+        Some(file) => std::fs::write(file, csv)?,
+        None => print!("{}", csv),
+    }
+    Ok(ExitCode::SUCCESS)
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let matches = Command::new("Grid Coloring Solver")
-        .version("1.0")
-        .author("Laurent Valdes <valderama@gmail.com>")
-        .about("Solves a grid coloring problem")
-        .arg(
-            Arg::new("input")
-                .short('i')
-                .default_value("small-grid.csv")
-                .long("input")
-                .value_name("FILE")
-                .help("Input CSV file"),
-        )
-        .arg(
-            Arg::new("output")
-                .short('o')
-                .default_value("output.csv")
-                .long("output")
-                .value_name("FILE")
-                .help("Output file for the solution"),
-        )
-        .arg(
-            Arg::new("output-grids")
-                .short('g')
-                .long("output-grids")
-                .action(ArgAction::SetTrue)
-                .help("Output the grids to the console"),
-        )
-        .get_matches();
+fn run_bench(registry: &Registry, matches: &ArgMatches) -> Result<ExitCode, Box<dyn Error>> {
+    let files: Vec<&String> = matches.get_many::<String>("files").into_iter().flatten().collect();
+    let generated = matches.get_one::<u64>("generate").copied().unwrap_or(if files.is_empty() { 5 } else { 0 });
+    let layout = pattern_from(matches)?;
+    let first_seed = *matches.get_one::<u64>("seed").expect("default seed");
+    let strategies: Vec<&str> = match matches.get_many::<String>("strategies") {
+        Some(names) => names.map(String::as_str).collect(),
+        None => registry.names(),
+    };
+    let options = SolverOptions { limits: limits_from(matches)?, ..SolverOptions::default() };
 
-    let input_file = matches.get_one::<String>("input").expect("required input file");
-    let output_file = matches.get_one::<String>("output");
-    let output_grids = matches.get_flag("output-grids");
+    let mut cases = Vec::new();
+    for file in files {
+        cases.push(Case { name: file.clone(), grid: load_grid(file, false)? });
+    }
+    for seed in first_seed..first_seed + generated {
+        let pattern = matches.get_one::<String>("pattern").expect("default pattern");
+        let mut name = format!("{}x{}-{}c-{}-{}", layout.width, layout.height, layout.colors, pattern, seed);
+        if layout.topology != Topology::Square {
+            name = format!("{}-{}", name, layout.topology);
+        }
+        cases.push(Case { name, grid: layout.generate(seed) });
+    }
 
-    let input = std::fs::read_to_string(input_file)?;
-    let mut grid = Grid::from_csv(&input).unwrap();
-    let solution = solve(&mut grid, output_grids);
-    save_solution(&solution, output_file.map(|x| x.as_str()))?;
+    let records = bench::run(registry, &strategies, &cases, &options, &mut |record| {
+        let stopped = record.stopped.map_or_else(String::new, |reason| format!(" ({})", reason));
+        println!("{} {}: {} moves in {:.3?}{}", record.case, record.strategy, record.moves, record.elapsed, stopped);
+    });
+    println!();
+    print!("{}", bench::summary(&records));
 
-    Ok(())
+    if let Some(file) = matches.get_one::<String>("csv") {
+        std::fs::write(file, bench::to_csv(&records))?;
+    }
+    if let Some(file) = matches.get_one::<String>("json") {
+        std::fs::write(file, bench::to_json(&records))?;
+    }
+    Ok(ExitCode::SUCCESS)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_simplest_input() {
-        let input = "0,1\n1,1";
-        let mut grid = Grid::from_csv(input).unwrap();
-        let solution = solve(&mut grid, false);
-        assert_eq!(solution, vec![1]);
-    }
-
-    #[test]
-    fn test_medium_input() {
-        let input = "2,1,3,0,4\n1,2,2,3,1\n0,3,1,2,4\n4,1,0,3,2\n3,2,4,1,0";
-        let mut grid = Grid::from_csv(input).unwrap();
-        let solution = solve(&mut grid, false);
-        let mut test_grid = grid.clone();
-        for &color in &solution {
-            test_grid.flood_fill(color);
+/// Reads moves from standard input for `duel`.
+struct Human;
+
+impl Agent for Human {
+    fn name(&self) -> String {
+        "human".to_string()
+    }
+
+    fn choose(&mut self, duel: &Duel) -> Option<u8> {
+        let legal = duel.legal_moves();
+        let player = duel.to_move();
+        loop {
+            print!("{}, pick a colour {:?} or q to resign: ", player, legal);
+            std::io::Write::flush(&mut std::io::stdout()).ok()?;
+            let mut line = String::new();
+            if std::io::stdin().read_line(&mut line).ok()? == 0 || line.trim() == "q" {
+                return None;
+            }
+            match line.trim().parse::<u8>() {
+                Ok(color) if duel.is_legal(color) => return Some(color),
+                _ => println!("{:?} is not a legal move", line.trim()),
+            }
         }
-        assert!(test_grid.is_complete());
-        assert!(solution.len() <= 16);
     }
+}
 
-    #[test]
-    fn test_sample_input() {
-        let input = "1,2,0,0\n0,1,1,0\n2,2,0,1\n0,0,0,1";
-        let mut grid = Grid::from_csv(input).unwrap();
-        let solution = solve(&mut grid, false);
-        assert_eq!(solution, vec![2, 1, 2, 0, 1]);
+fn duel_agent(spec: &str, seed: u64) -> Result<Box<dyn Agent>, Box<dyn Error>> {
+    match spec {
+        "human" => Ok(Box::new(Human)),
+        spec => Ok(adversarial::engine(spec, seed)?),
+    }
+}
+
+fn run_duel(matches: &ArgMatches) -> Result<ExitCode, Box<dyn Error>> {
+    let layout = pattern_from(matches)?;
+    let seed = *matches.get_one::<u64>("seed").expect("default seed");
+    let specs = ["first", "second"].map(|seat| matches.get_one::<String>(seat).expect("default engine"));
+    let mut first = duel_agent(specs[0], seed)?;
+    let mut second = duel_agent(specs[1], seed.wrapping_add(1))?;
+
+    let grids = match matches.get_one::<String>("grid") {
+        Some(file) => vec![load_grid(file, false)?],
+        None => {
+            let games = *matches.get_one::<u64>("games").expect("default game count");
+            (seed..seed + games).map(|seed| layout.generate(seed)).collect()
+        }
+    };
 
-        let mut test_grid = grid.clone();
-        assert!(test_grid.apply_solution(&solution));
+    if specs.iter().any(|&spec| spec == "human") {
+        let duel = Duel::new(&grids[0])?;
+        println!("{}", duel);
+        let outcome = duel::play_game(duel, [first.as_mut(), second.as_mut()], &mut |duel, color| {
+            println!("{} played {}", duel.to_move().other(), color);
+            println!("{}", duel);
+        });
+        match outcome.winner {
+            Some(player) if outcome.resigned => println!("{} wins by resignation", player),
+            Some(player) => println!("{} wins, {} cells to {}", player, outcome.owned[player.index()], outcome.owned[1 - player.index()]),
+            None => println!("Draw, {} cells each", outcome.owned[0]),
+        }
+        return Ok(ExitCode::SUCCESS);
     }
 
-    #[test]
-    fn test_flood_fill() {
-        let mut grid = Grid::new(3, 3, 3);
-        let original = DMatrix::from_vec(3, 3, vec![0, 0, 0, 1, 1, 1, 1, 0, 0]);
-        grid.data = original.clone();
-        grid.flood_fill(2);
-        let expected = DMatrix::from_vec(3, 3, vec![2, 2, 2, 1, 1, 1, 1, 0, 0]);
+    let names = [first.name(), second.name()];
+    let report = duel::run_match(&grids, [first.as_mut(), second.as_mut()], &mut |grid, starter, outcome| {
+        let result = match outcome.winner {
+            Some(player) => format!("{} wins", names[if player.index() == 0 { starter } else { 1 - starter }]),
+            None => "draw".to_string(),
+        };
+        println!(
+            "grid {}, {} first: {} ({} to {} cells, {} plies)",
+            grid + 1,
+            names[starter],
+            result,
+            outcome.owned[0],
+            outcome.owned[1],
+            outcome.plies
+        );
+    })?;
+    println!();
+    print!("{}", report);
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_play(registry: &Registry, matches: &ArgMatches) -> Result<ExitCode, Box<dyn Error>> {
+    let grid = load_grid(matches.get_one::<String>("grid").expect("required grid"), false)?;
+    let strategy = matches.get_one::<String>("strategy").expect("default strategy");
+    let solver = registry.get(strategy).expect("strategy validated by clap");
+    let output = matches.get_one::<String>("output").expect("default output").as_str();
+    let seconds = *matches.get_one::<f64>("time-limit").expect("default time limit");
+    let time = Duration::try_from_secs_f64(seconds).map_err(|_| format!("invalid time limit: {}", seconds))?;
+    let limits = Limits { time: Some(time), ..Limits::default() };
+    let options = SolverOptions { limits, ..SolverOptions::default() };
 
-        assert_eq!(grid.data, expected);
+    let mut best = solver.solve(&grid, &options, &mut |_| {}).moves.len();
+    let mut session = Session::new(grid);
+    let mut lines = std::io::stdin().lock().lines();
+    loop {
+        print!("{}", show(&session.current()));
+        if session.is_complete() {
+            println!("Flooded in {} moves, best known {} ({})", session.moves().len(), best.min(session.moves().len()), strategy);
+            save_solution(session.moves(), Some(output))?;
+            println!("Saved to {}", output);
+            return Ok(ExitCode::SUCCESS);
+        }
+        print!(
+            "Moves: {}, best known {} ({}). Colour 0-{}, u undo, r redo, h hint, s save, q quit: ",
+            session.moves().len(),
+            best,
+            strategy,
+            session.initial().colors() - 1
+        );
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next().transpose()? else {
+            println!();
+            return Ok(ExitCode::SUCCESS);
+        };
+        match line.trim() {
+            "q" => return Ok(ExitCode::SUCCESS),
+            "u" => {
+                if session.undo().is_none() {
+                    println!("Nothing to undo");
+                }
+            }
+            "r" => {
+                if session.redo().is_none() {
+                    println!("Nothing to redo");
+                }
+            }
+            "h" => match session.hint(solver, &options) {
+                Some((color, remaining)) => {
+                    best = best.min(session.moves().len() + remaining);
+                    println!("Hint: play {} ({} moves left this way)", color, remaining);
+                }
+                None => println!("No hint"),
+            },
+            "s" => {
+                save_solution(session.moves(), Some(output))?;
+                println!("Saved {} moves to {}", session.moves().len(), output);
+            }
+            input => match input.parse::<u8>() {
+                Ok(color) => {
+                    if let Err(err) = session.play(color) {
+                        println!("{}", err);
+                    }
+                }
+                Err(_) => println!("Unknown command {:?}", input),
+            },
+        }
     }
 }
@@ -0,0 +1,397 @@
+//! Monte Carlo strategies for boards too large for exact search: UCT tree
+//! search (MCTS), nested Monte Carlo search (NMCS) and nested rollout policy
+//! adaptation (NRPA), all built on playouts over the `RegionGraph`.
+//!
+//! Each strategy runs `roots` independent searches in parallel, seeded from
+//! one seed, and keeps the shortest solution; with the same options the
+//! result does not depend on the thread pool. Every search starts from the
+//! greedy solution as its incumbent, so none returns anything longer.
+
+use rayon::prelude::*;
+
+use crate::astar::SearchResult;
+use crate::bound::Bounds;
+use crate::budget::Budget;
+use crate::greedy::{self, GreedyScore};
+use crate::region::{FloodState, RegionGraph};
+use crate::rng::Rng;
+use crate::table::TableStats;
+use crate::Grid;
+
+/// How playouts pick their moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rollout {
+    /// Uniformly among the colours bordering the region.
+    Random,
+    /// With probability proportional to the cells each colour absorbs.
+    Greedy,
+}
+
+impl Rollout {
+    pub const NAMES: [&'static str; 2] = ["greedy", "random"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "random" => Some(Rollout::Random),
+            "greedy" => Some(Rollout::Greedy),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Mcts,
+    Nmcs,
+    Nrpa,
+}
+
+#[derive(Debug, Clone)]
+pub struct MonteCarloOptions {
+    pub seed: u64,
+    /// Playouts of each MCTS search, or iterations per NRPA level. `None`
+    /// picks 10000 for MCTS and 100 for NRPA; NMCS does not use it.
+    pub iterations: Option<usize>,
+    /// Nesting level of NMCS and NRPA.
+    pub level: usize,
+    /// Independent searches run in parallel.
+    pub roots: usize,
+    pub rollout: Rollout,
+}
+
+impl Default for MonteCarloOptions {
+    fn default() -> Self {
+        MonteCarloOptions { seed: 0, iterations: None, level: 2, roots: 1, rollout: Rollout::Greedy }
+    }
+}
+
+/// UCT exploration constant, for rewards between 0 and 1.
+const EXPLORATION: f64 = 0.5;
+
+/// NRPA learning rate.
+const ALPHA: f64 = 1.0;
+
+/// NRPA move weights, indexed by move number then colour.
+#[derive(Debug, Clone, Default)]
+struct Policy {
+    colors: usize,
+    weights: Vec<f64>,
+}
+
+impl Policy {
+    fn get(&self, t: usize, color: u8) -> f64 {
+        self.weights.get(t * self.colors + color as usize).copied().unwrap_or(0.0)
+    }
+
+    fn add(&mut self, t: usize, color: u8, delta: f64) {
+        let i = t * self.colors + color as usize;
+        if i >= self.weights.len() {
+            self.weights.resize(i + 1, 0.0);
+        }
+        self.weights[i] += delta;
+    }
+}
+
+struct Node {
+    parent: usize,
+    color: u8,
+    children: Vec<usize>,
+    untried: Vec<u8>,
+    visits: u32,
+    /// Sum of the playout rewards.
+    value: f64,
+}
+
+/// One search, with its own random stream.
+struct Search<'a> {
+    graph: &'a RegionGraph,
+    budget: &'a Budget,
+    rollout: Rollout,
+    rng: Rng,
+    bound: usize,
+    playouts: usize,
+}
+
+impl Search<'_> {
+    /// Moves bordering `state`, each with the state it leads to and its
+    /// playout weight when it is move `t`.
+    fn candidates(&self, state: &FloodState, t: usize, policy: Option<&Policy>) -> Vec<(u8, FloodState, f64)> {
+        self.graph
+            .frontier_colors(state)
+            .into_iter()
+            .map(|color| {
+                let next = self.graph.play(state, color);
+                let mut weight = policy.map_or(1.0, |policy| policy.get(t, color).exp());
+                if self.rollout == Rollout::Greedy {
+                    weight *= (next.cells - state.cells + 1) as f64;
+                }
+                (color, next, weight)
+            })
+            .collect()
+    }
+
+    /// Moves of a playout finishing the grid from `state`, reached after `t`
+    /// moves.
+    fn playout(&mut self, state: &FloodState, mut t: usize, policy: Option<&Policy>) -> Vec<u8> {
+        self.playouts += 1;
+        self.budget.expand();
+        let mut state = state.clone();
+        let mut moves = Vec::new();
+        while !self.graph.is_complete(&state) {
+            let (color, next) = if self.rollout == Rollout::Random && policy.is_none() {
+                let colors = self.graph.frontier_colors(&state);
+                let color = colors[self.rng.below(colors.len())];
+                (color, self.graph.play(&state, color))
+            } else {
+                let mut candidates = self.candidates(&state, t, policy);
+                let total: f64 = candidates.iter().map(|(_, _, weight)| weight).sum();
+                let mut x = self.rng.unit() * total;
+                let pick = candidates.iter().position(|(_, _, weight)| {
+                    x -= weight;
+                    x < 0.0
+                });
+                let (color, next, _) = candidates.swap_remove(pick.unwrap_or(0));
+                (color, next)
+            };
+            moves.push(color);
+            state = next;
+            t += 1;
+        }
+        moves
+    }
+
+    fn select(&self, nodes: &[Node], id: usize) -> usize {
+        let log_visits = (nodes[id].visits as f64).ln();
+        let uct = |c: usize| {
+            let child = &nodes[c];
+            child.value / child.visits as f64 + EXPLORATION * (log_visits / child.visits as f64).sqrt()
+        };
+        let children = &nodes[id].children;
+        children.iter().copied().fold(children[0], |best, c| if uct(c) > uct(best) { c } else { best })
+    }
+
+    /// UCT over move sequences. A playout of `n` moves is rewarded by where
+    /// `n` falls between the lower bound and the longest playout so far.
+    fn mcts(&mut self, iterations: usize, incumbent: Vec<u8>) -> Vec<u8> {
+        let initial = self.graph.initial_state();
+        let first = self.playout(&initial, 0, None);
+        let mut worst = first.len().max(incumbent.len());
+        let mut best = if first.len() < incumbent.len() { first } else { incumbent };
+        let root = Node { parent: usize::MAX, color: 0, children: Vec::new(), untried: self.graph.frontier_colors(&initial), visits: 0, value: 0.0 };
+        let mut nodes = vec![root];
+        for _ in 1..iterations {
+            if best.len() <= self.bound || self.budget.exhausted() {
+                break;
+            }
+            let mut state = initial.clone();
+            let mut line = Vec::new();
+            let mut id = 0;
+            while nodes[id].untried.is_empty() && !nodes[id].children.is_empty() {
+                id = self.select(&nodes, id);
+                state = self.graph.play(&state, nodes[id].color);
+                line.push(nodes[id].color);
+            }
+            if !nodes[id].untried.is_empty() {
+                let pick = self.rng.below(nodes[id].untried.len());
+                let color = nodes[id].untried.swap_remove(pick);
+                state = self.graph.play(&state, color);
+                line.push(color);
+                let untried = if self.graph.is_complete(&state) { Vec::new() } else { self.graph.frontier_colors(&state) };
+                nodes.push(Node { parent: id, color, children: Vec::new(), untried, visits: 0, value: 0.0 });
+                let child = nodes.len() - 1;
+                nodes[id].children.push(child);
+                id = child;
+            }
+            let rest = self.playout(&state, line.len(), None);
+            line.extend(rest);
+            worst = worst.max(line.len());
+            let reward = (worst - line.len() + 1) as f64 / (worst.saturating_sub(self.bound) + 1) as f64;
+            if line.len() < best.len() {
+                best = line;
+            }
+            while id != usize::MAX {
+                let node = &mut nodes[id];
+                node.visits += 1;
+                node.value += reward;
+                id = node.parent;
+            }
+        }
+        best
+    }
+
+    /// Nested Monte Carlo search: at every step, tries each move followed by
+    /// a search one level lower, and follows the best sequence found so far,
+    /// `incumbent` until a shorter one turns up.
+    fn nested(&mut self, state: &FloodState, t: usize, level: usize, incumbent: Option<Vec<u8>>) -> Vec<u8> {
+        if level == 0 {
+            return self.playout(state, t, None);
+        }
+        let mut state = state.clone();
+        let mut played = Vec::new();
+        let mut best = incumbent;
+        while !self.graph.is_complete(&state) {
+            let done = best.as_ref().is_some_and(|best| played.len() + best.len() <= self.bound);
+            if !done {
+                for color in self.graph.frontier_colors(&state) {
+                    if best.is_some() && self.budget.exhausted() {
+                        break;
+                    }
+                    let next = self.graph.play(&state, color);
+                    let mut line = vec![color];
+                    line.extend(self.nested(&next, t + played.len() + 1, level - 1, None));
+                    if best.as_ref().is_none_or(|best| line.len() < best.len()) {
+                        best = Some(line);
+                    }
+                }
+            }
+            let line = best.as_mut().expect("a move was tried");
+            let color = line.remove(0);
+            state = self.graph.play(&state, color);
+            played.push(color);
+        }
+        played
+    }
+
+    /// Nested rollout policy adaptation: each level runs the level below
+    /// `iterations` times, moving its policy towards the best sequence,
+    /// `incumbent` until a sequence as short turns up.
+    fn nrpa(&mut self, level: usize, mut policy: Policy, iterations: usize, incumbent: Option<Vec<u8>>) -> Vec<u8> {
+        let initial = self.graph.initial_state();
+        if level == 0 {
+            return self.playout(&initial, 0, Some(&policy));
+        }
+        let mut best = incumbent;
+        for _ in 0..iterations {
+            let line = self.nrpa(level - 1, policy.clone(), iterations, None);
+            if best.as_ref().is_none_or(|best| line.len() <= best.len()) {
+                best = Some(line);
+            }
+            let best = best.as_ref().expect("set above");
+            if best.len() <= self.bound || self.budget.exhausted() {
+                break;
+            }
+            self.adapt(&mut policy, best);
+        }
+        best.expect("at least one iteration")
+    }
+
+    fn adapt(&self, policy: &mut Policy, line: &[u8]) {
+        let old = policy.clone();
+        let mut state = self.graph.initial_state();
+        for (t, &color) in line.iter().enumerate() {
+            let candidates = self.candidates(&state, t, Some(&old));
+            let total: f64 = candidates.iter().map(|(_, _, weight)| weight).sum();
+            for (c, _, weight) in &candidates {
+                policy.add(t, *c, -ALPHA * weight / total);
+            }
+            policy.add(t, color, ALPHA);
+            state = self.graph.play(&state, color);
+        }
+    }
+}
+
+/// Runs `method` from `options.roots` independent seeds, each starting from
+/// the greedy solution, and keeps the shortest solution, the first root's on
+/// ties.
+pub fn solve(grid: &Grid, method: Method, options: &MonteCarloOptions, budget: &Budget) -> SearchResult {
+    let graph = RegionGraph::new(grid);
+    let bound = Bounds::compute(&graph, &graph.initial_state()).value();
+    let greedy = greedy::complete_greedily(&graph, &graph.initial_state(), GreedyScore::Cells, budget);
+    let mut seeds = Rng::new(options.seed);
+    let seeds: Vec<u64> = (0..options.roots.max(1)).map(|_| seeds.next_u64()).collect();
+
+    let results: Vec<(Vec<u8>, usize)> = seeds
+        .into_par_iter()
+        .map(|seed| {
+            let mut search = Search { graph: &graph, budget, rollout: options.rollout, rng: Rng::new(seed), bound, playouts: 0 };
+            let moves = match method {
+                Method::Mcts => search.mcts(options.iterations.unwrap_or(10_000).max(1), greedy.clone()),
+                Method::Nmcs => search.nested(&graph.initial_state(), 0, options.level, Some(greedy.clone())),
+                Method::Nrpa => {
+                    let policy = Policy { colors: graph.colors, weights: Vec::new() };
+                    search.nrpa(options.level, policy, options.iterations.unwrap_or(100).max(1), Some(greedy.clone()))
+                }
+            };
+            (moves, search.playouts)
+        })
+        .collect();
+
+    let playouts = results.iter().map(|(_, playouts)| playouts).sum();
+    let moves = results.into_iter().map(|(moves, _)| moves).reduce(|best, moves| if moves.len() < best.len() { moves } else { best });
+    let moves = moves.unwrap_or(greedy);
+    SearchResult { moves, proven_optimal: false, nodes_expanded: playouts, table: TableStats::default() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{generate, Pattern};
+
+    const METHODS: [Method; 3] = [Method::Mcts, Method::Nmcs, Method::Nrpa];
+
+    fn options(rollout: Rollout, roots: usize) -> MonteCarloOptions {
+        MonteCarloOptions { seed: 3, iterations: Some(30), level: 1, roots, rollout }
+    }
+
+    #[test]
+    fn test_solutions_flood_the_grid() {
+        let grid = generate(10, 10, 5, Pattern::Uniform, 2);
+        for method in METHODS {
+            for rollout in [Rollout::Random, Rollout::Greedy] {
+                let result = solve(&grid, method, &options(rollout, 2), &Budget::unlimited());
+                assert!(grid.clone().apply_solution(&result.moves), "{:?} {:?}", method, rollout);
+                assert!(result.nodes_expanded > 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_same_seed_same_solution() {
+        let grid = generate(10, 10, 5, Pattern::Uniform, 5);
+        for method in METHODS {
+            let a = solve(&grid, method, &options(Rollout::Random, 3), &Budget::unlimited());
+            let b = solve(&grid, method, &options(Rollout::Random, 3), &Budget::unlimited());
+            assert_eq!(a.moves, b.moves, "{:?}", method);
+            assert_eq!(a.nodes_expanded, b.nodes_expanded);
+        }
+    }
+
+    #[test]
+    fn test_finds_the_optimum_of_the_sample() {
+        let grid = Grid::from_csv("1,2,0,0\n0,1,1,0\n2,2,0,1\n0,0,0,1").unwrap();
+        for method in METHODS {
+            assert_eq!(solve(&grid, method, &options(Rollout::Random, 1), &Budget::unlimited()).moves.len(), 4, "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn test_never_worse_than_greedy() {
+        let grid = generate(40, 40, 6, Pattern::Uniform, 11);
+        let greedy = greedy::solve_greedy(&grid, GreedyScore::Cells, &Budget::unlimited());
+        let options = MonteCarloOptions { iterations: Some(5), ..options(Rollout::Random, 2) };
+        for method in METHODS {
+            let result = solve(&grid, method, &options, &Budget::unlimited());
+            assert!(grid.is_solution(&result.moves), "{:?}", method);
+            assert!(result.moves.len() <= greedy.len(), "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn test_searches_beat_average_playouts() {
+        let grid = generate(12, 12, 6, Pattern::Uniform, 8);
+        let mut search = Search {
+            graph: &RegionGraph::new(&grid),
+            budget: &Budget::unlimited(),
+            rollout: Rollout::Random,
+            rng: Rng::new(1),
+            bound: 0,
+            playouts: 0,
+        };
+        let initial = search.graph.initial_state();
+        let random = (0..20).map(|_| search.playout(&initial, 0, None).len()).sum::<usize>() / 20;
+        let options = MonteCarloOptions { iterations: Some(200), ..options(Rollout::Random, 1) };
+        for method in METHODS {
+            assert!(solve(&grid, method, &options, &Budget::unlimited()).moves.len() < random, "{:?}", method);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rayon::prelude::*;
+
+use crate::astar::SearchResult;
+use crate::bound::Bounds;
+use crate::budget::Budget;
+use crate::greedy::{self, GreedyScore};
+use crate::region::{FloodState, RegionGraph};
+use crate::table::{TableStats, TranspositionTable};
+use crate::Grid;
+
+/// Depth-first branch and bound below one first move.
+struct Branch<'a> {
+    graph: &'a RegionGraph,
+    budget: &'a Budget,
+    /// Length of the shortest solution found by any branch so far.
+    global_best: &'a AtomicUsize,
+    best: Option<Vec<u8>>,
+    seen: TranspositionTable,
+    nodes_expanded: usize,
+}
+
+impl Branch<'_> {
+    fn search(&mut self, state: &FloodState, moves: &mut Vec<u8>) {
+        // Ties with other branches are kept so that every branch reports its
+        // own first optimal line, which makes the final pick independent of
+        // thread timing. Within the branch only strict improvements matter.
+        let f = moves.len() + Bounds::compute(self.graph, state).value();
+        let local_best = self.best.as_ref().map_or(usize::MAX, Vec::len);
+        if f > self.global_best.load(Ordering::Relaxed) || f >= local_best {
+            return;
+        }
+        if self.graph.is_complete(state) {
+            self.global_best.fetch_min(moves.len(), Ordering::Relaxed);
+            self.best = Some(moves.clone());
+            return;
+        }
+        if self.seen.probe(state).is_some_and(|d| d <= moves.len()) {
+            return;
+        }
+        if self.budget.expand() {
+            return;
+        }
+        self.seen.store(state.clone(), moves.len());
+        self.nodes_expanded += 1;
+
+        let mut children: Vec<(u8, FloodState)> = self
+            .graph
+            .frontier_colors(state)
+            .into_iter()
+            .map(|color| (color, self.graph.play(state, color)))
+            .collect();
+        children.sort_by_key(|(color, next)| (std::cmp::Reverse(next.cells), *color));
+
+        for (color, next) in children {
+            moves.push(color);
+            self.search(&next, moves);
+            moves.pop();
+            if self.budget.stop_reason().is_some() {
+                break;
+            }
+        }
+    }
+}
+
+/// Optimal branch and bound with the first moves explored in parallel.
+///
+/// The greedy solution seeds the shared bound. Each first move gets its own
+/// depth-first search and a share of the transposition table memory; they
+/// only share the length of the best solution found so far through an atomic.
+/// If the budget runs out, the best solution found so far is returned.
+pub fn solve_branch_and_bound(
+    grid: &Grid,
+    table_bytes: usize,
+    budget: &Budget,
+    on_improve: &mut dyn FnMut(&[u8]),
+) -> SearchResult {
+    let graph = RegionGraph::new(grid);
+    let root = graph.initial_state();
+    if graph.is_complete(&root) {
+        let table = TableStats::default();
+        return SearchResult { moves: Vec::new(), proven_optimal: true, nodes_expanded: 0, table };
+    }
+
+    let incumbent = greedy::complete_greedily(&graph, &root, GreedyScore::Cells, budget);
+    on_improve(&incumbent);
+    let global_best = AtomicUsize::new(incumbent.len());
+
+    let first_moves = graph.frontier_colors(&root);
+    let branch_bytes = table_bytes / first_moves.len();
+
+    let branches: Vec<(Option<Vec<u8>>, usize, TableStats)> = first_moves
+        .into_par_iter()
+        .map(|color| {
+            let mut branch = Branch {
+                graph: &graph,
+                budget,
+                global_best: &global_best,
+                best: None,
+                seen: TranspositionTable::new(branch_bytes, graph.component_count()),
+                nodes_expanded: 0,
+            };
+            branch.search(&graph.play(&root, color), &mut vec![color]);
+            (branch.best, branch.nodes_expanded, branch.seen.stats())
+        })
+        .collect();
+
+    let nodes_expanded = 1 + branches.iter().map(|(_, n, _)| n).sum::<usize>();
+    let mut table = TableStats::default();
+    for (_, _, stats) in &branches {
+        table.merge(stats);
+    }
+    let moves = branches
+        .into_iter()
+        .filter_map(|(best, _, _)| best)
+        .min_by_key(Vec::len)
+        .filter(|best| best.len() < incumbent.len())
+        .unwrap_or(incumbent);
+
+    SearchResult { moves, proven_optimal: budget.stop_reason().is_none(), nodes_expanded, table }
+}
+
+/// Runs the heuristic strategies concurrently and keeps the shortest result.
+/// Ties go to the strategy listed first.
+pub fn solve_portfolio(
+    grid: &Grid,
+    lookahead: usize,
+    beam_width: usize,
+    budget: &Budget,
+) -> (&'static str, Vec<u8>) {
+    let strategies: [&'static str; 4] = ["greedy", "greedy-colors", "lookahead", "beam"];
+
+    strategies
+        .par_iter()
+        .map(|&name| {
+            let moves = match name {
+                "greedy" => greedy::solve_greedy(grid, GreedyScore::Cells, budget),
+                "greedy-colors" => greedy::solve_greedy(grid, GreedyScore::ColorClasses, budget),
+                "lookahead" => greedy::solve_lookahead(grid, lookahead, budget),
+                _ => greedy::solve_beam(grid, beam_width, budget),
+            };
+            (name, moves)
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .min_by_key(|(_, moves)| moves.len())
+        .expect("portfolio is not empty")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::astar;
+    use crate::table::DEFAULT_TABLE_BYTES;
+
+    const MEDIUM: &str = "2,1,3,0,4\n1,2,2,3,1\n0,3,1,2,4\n4,1,0,3,2\n3,2,4,1,0";
+
+    #[test]
+    fn test_branch_and_bound_is_optimal() {
+        for input in ["1,2,0,0\n0,1,1,0\n2,2,0,1\n0,0,0,1", MEDIUM] {
+            let grid = Grid::from_csv(input).unwrap();
+            let budget = Budget::unlimited();
+            let result = solve_branch_and_bound(&grid, DEFAULT_TABLE_BYTES, &budget, &mut |_| {});
+            assert!(grid.clone().apply_solution(&result.moves));
+            let optimal = astar::solve_astar(&grid, DEFAULT_TABLE_BYTES, &budget, &mut |_| {});
+            assert_eq!(result.moves.len(), optimal.moves.len());
+        }
+    }
+
+    #[test]
+    fn test_branch_and_bound_is_deterministic_across_pools() {
+        let grid = Grid::from_csv(MEDIUM).unwrap();
+        let run = |threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| {
+                    solve_branch_and_bound(&grid, DEFAULT_TABLE_BYTES, &Budget::unlimited(), &mut |_| {}).moves
+                })
+        };
+        assert_eq!(run(1), run(4));
+    }
+
+    #[test]
+    fn test_portfolio_beats_each_member() {
+        let grid = Grid::from_csv(MEDIUM).unwrap();
+        let (_, moves) = solve_portfolio(&grid, 2, 4, &Budget::unlimited());
+        assert!(grid.clone().apply_solution(&moves));
+        assert!(moves.len() <= greedy::solve_greedy(&grid, GreedyScore::Cells, &Budget::unlimited()).len());
+    }
+}
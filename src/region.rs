@@ -0,0 +1,399 @@
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+
+use nalgebra::DMatrix;
+
+use crate::rng::Rng;
+use crate::topology::Topology;
+use crate::Grid;
+
+/// Fixed-size set of component indices.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    pub fn new(len: usize) -> Self {
+        BitSet { words: vec![0; len.div_ceil(64)] }
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    /// Inserts `i`, returning `false` if it was already present.
+    pub fn insert(&mut self, i: usize) -> bool {
+        let word = &mut self.words[i / 64];
+        let bit = 1 << (i % 64);
+        let added = *word & bit == 0;
+        *word |= bit;
+        added
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(i * 64 + bit)
+            })
+        })
+    }
+}
+
+/// Region adjacency graph of a grid: every maximal same-colour connected
+/// component is a node, and nodes are linked when their cells touch in the
+/// grid's topology.
+#[derive(Debug, Clone)]
+pub struct RegionGraph {
+    pub width: usize,
+    pub height: usize,
+    pub colors: usize,
+    pub topology: Topology,
+    /// Number of cells that are not walls.
+    pub open_cells: usize,
+    /// Component index of each cell, row-major, `usize::MAX` for walls.
+    pub labels: Vec<usize>,
+    /// Colour of each component.
+    pub component_colors: Vec<u8>,
+    /// Number of cells in each component.
+    pub sizes: Vec<usize>,
+    /// Sorted, deduplicated neighbour lists.
+    pub adjacency: Vec<Vec<usize>>,
+    /// Starting cells of the grid, as `(x, y)`.
+    pub origins: Vec<(usize, usize)>,
+    /// Components holding an origin, deduplicated, the first origin's first.
+    pub origin_components: Vec<usize>,
+    /// Zobrist keys: one per component, one per colour, then one for mixed
+    /// states.
+    zobrist: Vec<u64>,
+}
+
+/// Flooded region expressed over a `RegionGraph`: the absorbed components and
+/// the colour they currently share.
+///
+/// On a grid whose origins have different colours, the initial state is
+/// mixed: each origin component keeps its colour and `color` is the first
+/// one's. Any move ends that.
+///
+/// The components bordering the region are kept bucketed by colour, so
+/// playing a colour only visits the components it absorbs.
+#[derive(Debug, Clone)]
+pub struct FloodState {
+    pub absorbed: BitSet,
+    /// Components that are absorbed or sit in a frontier bucket.
+    reached: BitSet,
+    /// Components bordering the region, indexed by colour.
+    frontier: Vec<Vec<usize>>,
+    /// Components of each colour not yet absorbed.
+    remaining: Vec<usize>,
+    pub color: u8,
+    pub mixed: bool,
+    pub cells: usize,
+    /// Zobrist hash of `absorbed`, `color` and `mixed`, maintained by
+    /// `RegionGraph::play`.
+    pub hash: u64,
+}
+
+/// The frontier is derived from `absorbed`, so it takes no part in equality.
+impl PartialEq for FloodState {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && self.color == other.color && self.mixed == other.mixed && self.absorbed == other.absorbed
+    }
+}
+
+impl Eq for FloodState {}
+
+impl Hash for FloodState {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+impl RegionGraph {
+    pub fn new(grid: &Grid) -> Self {
+        let (width, height) = (grid.width, grid.height);
+        let mut labels = vec![usize::MAX; width * height];
+        let mut component_colors = Vec::new();
+        let mut sizes = Vec::new();
+        let mut stack = Vec::new();
+
+        for y in 0..height {
+            for x in 0..width {
+                if labels[y * width + x] != usize::MAX || grid.is_wall(x, y) {
+                    continue;
+                }
+                let id = component_colors.len();
+                let color = grid.data[(y, x)];
+                let mut size = 0;
+                labels[y * width + x] = id;
+                stack.push((x, y));
+                while let Some((cx, cy)) = stack.pop() {
+                    size += 1;
+                    for (nx, ny) in grid.neighbours(cx, cy) {
+                        let idx = ny * width + nx;
+                        if labels[idx] == usize::MAX && grid.data[(ny, nx)] == color {
+                            labels[idx] = id;
+                            stack.push((nx, ny));
+                        }
+                    }
+                }
+                component_colors.push(color);
+                sizes.push(size);
+            }
+        }
+
+        let mut adjacency = vec![Vec::new(); component_colors.len()];
+        for y in 0..height {
+            for x in 0..width {
+                let a = labels[y * width + x];
+                if a == usize::MAX {
+                    continue;
+                }
+                for (nx, ny) in grid.neighbours(x, y) {
+                    let b = labels[ny * width + nx];
+                    if a != b {
+                        adjacency[a].push(b);
+                    }
+                }
+            }
+        }
+        for neighbours in &mut adjacency {
+            neighbours.sort_unstable();
+            neighbours.dedup();
+        }
+
+        let mut rng = Rng::new(0x00C0_10A1_7F10_0D17);
+        let zobrist = (0..component_colors.len() + 257).map(|_| rng.next_u64()).collect();
+
+        let mut origin_components = Vec::new();
+        for &(x, y) in grid.origins() {
+            let c = labels[y * width + x];
+            if !origin_components.contains(&c) {
+                origin_components.push(c);
+            }
+        }
+
+        let colors = component_colors.iter().map(|&c| c as usize + 1).max().unwrap_or(0).max(grid.colors);
+        let open_cells = grid.open_cells();
+        let topology = grid.topology;
+        let origins = grid.origins.clone();
+        RegionGraph {
+            width,
+            height,
+            colors,
+            topology,
+            open_cells,
+            labels,
+            component_colors,
+            sizes,
+            adjacency,
+            origins,
+            origin_components,
+            zobrist,
+        }
+    }
+
+    pub fn component_count(&self) -> usize {
+        self.component_colors.len()
+    }
+
+    /// State before any move: only the components holding an origin are
+    /// flooded.
+    pub fn initial_state(&self) -> FloodState {
+        let color = self.component_colors[self.origin_components[0]];
+        let mixed = self.origin_components.iter().any(|&c| self.component_colors[c] != color);
+        let mut state = FloodState {
+            absorbed: BitSet::new(self.component_count()),
+            reached: BitSet::new(self.component_count()),
+            frontier: vec![Vec::new(); self.colors],
+            remaining: vec![0; self.colors],
+            color,
+            mixed,
+            cells: 0,
+            hash: self.color_key(color) ^ if mixed { self.mixed_key() } else { 0 },
+        };
+        for &c in &self.component_colors {
+            state.remaining[c as usize] += 1;
+        }
+        for &origin in &self.origin_components {
+            state.reached.insert(origin);
+        }
+        for &origin in &self.origin_components {
+            self.absorb(&mut state, origin);
+        }
+        state
+    }
+
+    /// Plays `color`: the flooded region takes the colour and absorbs every
+    /// neighbouring component of that colour.
+    pub fn play(&self, state: &FloodState, color: u8) -> FloodState {
+        let mut next = state.clone();
+        next.color = color;
+        next.mixed = false;
+        next.hash ^= self.color_key(state.color) ^ self.color_key(color);
+        if state.mixed {
+            next.hash ^= self.mixed_key();
+        }
+        if let Some(bucket) = next.frontier.get_mut(color as usize) {
+            for c in std::mem::take(bucket) {
+                self.absorb(&mut next, c);
+            }
+        }
+        next
+    }
+
+    /// Adds component `c` to the region and files its unseen neighbours into
+    /// the frontier. Adjacent components never share a colour, so none of
+    /// them lands in the bucket being absorbed.
+    fn absorb(&self, state: &mut FloodState, c: usize) {
+        state.absorbed.insert(c);
+        state.remaining[self.component_colors[c] as usize] -= 1;
+        state.cells += self.sizes[c];
+        state.hash ^= self.zobrist[c];
+        for &n in &self.adjacency[c] {
+            if state.reached.insert(n) {
+                state.frontier[self.component_colors[n] as usize].push(n);
+            }
+        }
+    }
+
+    fn color_key(&self, color: u8) -> u64 {
+        self.zobrist[self.component_count() + color as usize]
+    }
+
+    fn mixed_key(&self) -> u64 {
+        self.zobrist[self.component_count() + 256]
+    }
+
+    /// Sorted colours of the components bordering the flooded region. In a
+    /// mixed state the origins' colours are moves too, since playing one
+    /// still recolours the other origins.
+    pub fn frontier_colors(&self, state: &FloodState) -> Vec<u8> {
+        let origin_color = |c: usize| state.mixed && self.origin_components.iter().any(|&o| self.component_colors[o] as usize == c);
+        (0..state.frontier.len()).filter(|&c| !state.frontier[c].is_empty() || origin_color(c)).map(|c| c as u8).collect()
+    }
+
+    /// Number of distinct colours outside the flooded region.
+    pub fn colors_remaining(&self, state: &FloodState) -> usize {
+        state.remaining.iter().filter(|&&n| n > 0).count()
+    }
+
+    pub fn is_complete(&self, state: &FloodState) -> bool {
+        state.cells == self.open_cells && !state.mixed
+    }
+
+    /// Hop distance from the flooded region to every component.
+    pub fn distances(&self, state: &FloodState) -> Vec<usize> {
+        let mut dist = vec![usize::MAX; self.component_count()];
+        for c in state.absorbed.iter() {
+            dist[c] = 0;
+        }
+        let mut queue = VecDeque::new();
+        for &c in state.frontier.iter().flatten() {
+            dist[c] = 1;
+            queue.push_back(c);
+        }
+        while let Some(c) = queue.pop_front() {
+            for &n in &self.adjacency[c] {
+                if dist[n] == usize::MAX {
+                    dist[n] = dist[c] + 1;
+                    queue.push_back(n);
+                }
+            }
+        }
+        dist
+    }
+
+    /// Grid a `FloodState` corresponds to, for verification against `Grid::flood_fill`.
+    pub fn to_grid(&self, state: &FloodState) -> Grid {
+        let mut data = DMatrix::zeros(self.height, self.width);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let c = self.labels[y * self.width + x];
+                data[(y, x)] = match c {
+                    usize::MAX => 0,
+                    c if state.absorbed.contains(c) && !state.mixed => state.color,
+                    c => self.component_colors[c],
+                };
+            }
+        }
+        let walls = self.labels.iter().map(|&c| c == usize::MAX).collect();
+        Grid {
+            width: self.width,
+            height: self.height,
+            colors: self.colors,
+            data,
+            walls,
+            topology: self.topology,
+            origins: self.origins.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{generate, Pattern};
+    use crate::Flood;
+
+    const SAMPLE: &str = "1,2,0,0\n0,1,1,0\n2,2,0,1\n0,0,0,1";
+
+    #[test]
+    fn test_components_of_sample() {
+        let graph = RegionGraph::new(&Grid::from_csv(SAMPLE).unwrap());
+        assert_eq!(graph.component_count(), 8);
+        assert_eq!(graph.sizes.iter().sum::<usize>(), 16);
+        assert_eq!(graph.frontier_colors(&graph.initial_state()), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_play_matches_flood_fill() {
+        let grid = Grid::from_csv(SAMPLE).unwrap();
+        let graph = RegionGraph::new(&grid);
+        let mut state = graph.initial_state();
+        let mut reference = grid.clone();
+        for color in [0, 2, 0, 1] {
+            state = graph.play(&state, color);
+            reference.flood_fill(color);
+            assert_eq!(graph.to_grid(&state).data, reference.data);
+        }
+        assert!(graph.is_complete(&state));
+    }
+
+    #[test]
+    fn test_play_matches_incremental_flood() {
+        for origins in [vec![(0, 0)], vec![(7, 6)], vec![(0, 0), (14, 11), (7, 0)]] {
+            let mut grid = generate(15, 12, 5, Pattern::Uniform, 9);
+            grid.set_origins(&origins);
+            let graph = RegionGraph::new(&grid);
+            let mut state = graph.initial_state();
+            let mut flood = Flood::new(&grid);
+            assert_eq!(graph.to_grid(&state).data, grid.data);
+            while !graph.is_complete(&state) {
+                assert_eq!(graph.frontier_colors(&state), flood.frontier_colors());
+                let color = graph.frontier_colors(&state)[state.cells % graph.frontier_colors(&state).len()];
+                state = graph.play(&state, color);
+                flood.play(color);
+                assert_eq!(state.cells, flood.flooded_cells());
+                assert_eq!(graph.to_grid(&state).data, flood.to_grid().data);
+            }
+            assert!(flood.is_complete());
+            assert_eq!(graph.colors_remaining(&state), 0);
+        }
+    }
+
+    #[test]
+    fn test_bitset() {
+        let mut set = BitSet::new(130);
+        assert!(set.insert(3));
+        assert!(set.insert(129));
+        assert!(!set.insert(3));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![3, 129]);
+    }
+}
@@ -0,0 +1,120 @@
+//! Colour palette and terminal rendering of grids.
+
+use crate::Grid;
+
+/// Distinct, readable colours for the first sixteen colour indices.
+const PALETTE: [[u8; 3]; 16] = [
+    [230, 57, 70],
+    [69, 123, 157],
+    [255, 183, 3],
+    [42, 157, 143],
+    [144, 80, 180],
+    [244, 162, 97],
+    [38, 70, 83],
+    [168, 218, 220],
+    [106, 153, 78],
+    [233, 196, 106],
+    [181, 23, 158],
+    [120, 94, 62],
+    [76, 201, 240],
+    [255, 112, 166],
+    [190, 190, 190],
+    [20, 33, 61],
+];
+
+/// Drawn for walls.
+pub const WALL: [u8; 3] = [24, 24, 24];
+
+/// RGB colour of colour index `color`. Indices beyond the built-in palette
+/// walk around the hue circle by the golden angle.
+pub fn rgb(color: u8) -> [u8; 3] {
+    if let Some(&rgb) = PALETTE.get(color as usize) {
+        return rgb;
+    }
+    let hue = (color as f64 * 137.507_764) % 360.0;
+    let lightness = if color.is_multiple_of(2) { 0.45 } else { 0.65 };
+    hsl(hue, 0.65, lightness)
+}
+
+fn hsl(hue: f64, saturation: f64, lightness: f64) -> [u8; 3] {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    [r, g, b].map(|c| ((c + m) * 255.0).round() as u8)
+}
+
+/// Black or white, whichever reads better on `background`.
+fn foreground(background: [u8; 3]) -> [u8; 3] {
+    let [r, g, b] = background.map(f64::from);
+    if 0.299 * r + 0.587 * g + 0.114 * b > 150.0 {
+        [0, 0, 0]
+    } else {
+        [255, 255, 255]
+    }
+}
+
+/// The grid drawn with 24-bit ANSI background colours, each cell labelled
+/// with its colour index. Hexagonal rows are indented like `Grid`'s Display.
+pub fn ansi(grid: &Grid) -> String {
+    let cell_width = (grid.colors().max(1) - 1).to_string().len() + 1;
+    let mut out = String::new();
+    for y in 0..grid.height() {
+        let indent = grid.topology().row_shift(y) * cell_width / 2;
+        out.push_str(&" ".repeat(indent));
+        for x in 0..grid.width() {
+            let (label, background) = if grid.is_wall(x, y) {
+                (String::new(), WALL)
+            } else {
+                (grid.get(x, y).to_string(), rgb(grid.get(x, y)))
+            };
+            let [fr, fg, fb] = foreground(background);
+            let [br, bg, bb] = background;
+            out.push_str(&format!(
+                "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m{:>w$}",
+                fr,
+                fg,
+                fb,
+                br,
+                bg,
+                bb,
+                label,
+                w = cell_width
+            ));
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colours_are_distinct() {
+        let colors: Vec<[u8; 3]> = (0..=u8::MAX).map(rgb).collect();
+        for (i, a) in colors.iter().enumerate().take(64) {
+            assert!(colors[..i].iter().all(|b| b != a), "colour {} repeats", i);
+        }
+        assert_eq!(hsl(0.0, 1.0, 0.5), [255, 0, 0]);
+        assert_eq!(hsl(240.0, 1.0, 0.5), [0, 0, 255]);
+    }
+
+    #[test]
+    fn test_ansi() {
+        let grid = Grid::from_csv("0,1\n#,12").unwrap();
+        let text = ansi(&grid);
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.starts_with("\x1b[38;2;255;255;255m\x1b[48;2;230;57;70m  0"));
+        assert!(text.contains("\x1b[48;2;24;24;24m   "));
+        assert!(text.lines().all(|line| line.ends_with("\x1b[0m")));
+    }
+}
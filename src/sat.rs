@@ -0,0 +1,487 @@
+//! Encodings of "can this grid be flooded in at most `k` moves" for external
+//! solvers: DIMACS CNF for SAT solvers and CPLEX LP for ILP solvers, the
+//! decoding of a satisfying assignment back into moves, and a driver that
+//! searches over `k` by running a SAT solver binary.
+//!
+//! The model works on the `RegionGraph`. For every step `t` from 1 to `k`
+//! and colour `c`, `m(t, c)` says that move `t` plays `c`; for every `t` from
+//! 0 to `k` and component `v`, `a(t, v)` says that `v` is flooded after `t`
+//! moves. At most one colour is played per step, so a step may also be left
+//! empty, and `v` is flooded after move `t` exactly when it already was, or
+//! when move `t` plays its colour and one of its neighbours was flooded.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::bound::Bounds;
+use crate::budget::Budget;
+use crate::greedy::{self, GreedyScore};
+use crate::region::RegionGraph;
+use crate::Grid;
+
+/// The CNF asking whether a grid can be flooded in at most `steps` moves.
+#[derive(Debug, Clone)]
+pub struct Encoding {
+    graph: RegionGraph,
+    pub steps: usize,
+}
+
+impl Encoding {
+    pub fn new(grid: &Grid, steps: usize) -> Self {
+        Encoding { graph: RegionGraph::new(grid), steps }
+    }
+
+    /// Variable of `m(t, c)`, for `t` in `1..=steps`.
+    pub fn move_var(&self, t: usize, color: u8) -> i64 {
+        ((t - 1) * self.graph.colors + color as usize + 1) as i64
+    }
+
+    /// Variable of `a(t, v)`, for `t` in `0..=steps`.
+    pub fn flooded_var(&self, t: usize, component: usize) -> i64 {
+        (self.steps * self.graph.colors + t * self.graph.component_count() + component + 1) as i64
+    }
+
+    pub fn variables(&self) -> usize {
+        self.steps * self.graph.colors + (self.steps + 1) * self.graph.component_count()
+    }
+
+    /// The clauses, as DIMACS literals without the terminating 0.
+    pub fn clauses(&self) -> Vec<Vec<i64>> {
+        let graph = &self.graph;
+        let (k, colors) = (self.steps, graph.colors as u8);
+        let initial = graph.initial_state();
+        let dist = graph.distances(&initial);
+        let mut clauses = Vec::new();
+
+        // Start, goal, and components too far to be flooded yet
+        for (v, &d) in dist.iter().enumerate() {
+            let a0 = self.flooded_var(0, v);
+            clauses.push(vec![if initial.absorbed.contains(v) { a0 } else { -a0 }]);
+            for t in 1..=k.min(d.saturating_sub(1)) {
+                clauses.push(vec![-self.flooded_var(t, v)]);
+            }
+            clauses.push(vec![self.flooded_var(k, v)]);
+        }
+        // Origins of different colours only end up one colour after a move
+        if initial.mixed {
+            clauses.push(if k == 0 { Vec::new() } else { (0..colors).map(|c| self.move_var(1, c)).collect() });
+        }
+
+        for t in 1..=k {
+            for c in 0..colors {
+                for d in c + 1..colors {
+                    clauses.push(vec![-self.move_var(t, c), -self.move_var(t, d)]);
+                }
+            }
+            for v in 0..graph.component_count() {
+                let (before, after) = (self.flooded_var(t - 1, v), self.flooded_var(t, v));
+                let played = self.move_var(t, graph.component_colors[v]);
+                clauses.push(vec![-before, after]);
+                clauses.push(vec![-after, before, played]);
+                let mut reached = vec![-after, before];
+                for &u in &graph.adjacency[v] {
+                    let neighbour = self.flooded_var(t - 1, u);
+                    clauses.push(vec![-played, -neighbour, after]);
+                    reached.push(neighbour);
+                }
+                clauses.push(reached);
+            }
+        }
+        clauses
+    }
+
+    pub fn write_dimacs<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let clauses = self.clauses();
+        writeln!(writer, "c Flood-It in at most {} moves", self.steps)?;
+        writeln!(
+            writer,
+            "c {} colours, {} components: m(t,c) = (t-1)*{} + c + 1, a(t,v) = {} + t*{} + v + 1",
+            self.graph.colors,
+            self.graph.component_count(),
+            self.graph.colors,
+            self.steps * self.graph.colors,
+            self.graph.component_count()
+        )?;
+        writeln!(writer, "p cnf {} {}", self.variables(), clauses.len())?;
+        for clause in &clauses {
+            for lit in clause {
+                write!(writer, "{} ", lit)?;
+            }
+            writeln!(writer, "0")?;
+        }
+        Ok(())
+    }
+
+    /// LP name of a variable: `m<t>_<c>` or `a<t>_<v>`.
+    fn lp_name(&self, var: i64) -> String {
+        let i = var as usize - 1;
+        let moves = self.steps * self.graph.colors;
+        if i < moves {
+            format!("m{}_{}", i / self.graph.colors + 1, i % self.graph.colors)
+        } else {
+            let i = i - moves;
+            format!("a{}_{}", i / self.graph.component_count(), i % self.graph.component_count())
+        }
+    }
+
+    /// Writes the same model as an integer program in CPLEX LP format, every
+    /// clause becoming a covering constraint. The objective counts the moves
+    /// played, so with `steps` at least the optimum, the solver's optimum is
+    /// an optimal solution.
+    pub fn write_lp<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        // Move variables come first
+        let moves = 1..=(self.steps * self.graph.colors) as i64;
+        writeln!(writer, "\\ Flood-It in at most {} moves", self.steps)?;
+        writeln!(writer, "Minimize")?;
+        write!(writer, " moves:")?;
+        if moves.is_empty() {
+            write!(writer, " 0 {}", self.lp_name(1))?;
+        }
+        write_terms(&mut writer, moves.enumerate().map(|(i, m)| format!("{}{}", if i > 0 { "+ " } else { "" }, self.lp_name(m))))?;
+        writeln!(writer, "\nSubject To")?;
+        for (i, clause) in self.clauses().iter().enumerate() {
+            write!(writer, " c{}:", i + 1)?;
+            if clause.is_empty() {
+                write!(writer, " 0 {}", self.lp_name(1))?;
+            }
+            let terms = clause.iter().enumerate().map(|(j, &lit)| {
+                let sign = if lit < 0 { "- " } else if j > 0 { "+ " } else { "" };
+                format!("{}{}", sign, self.lp_name(lit.abs()))
+            });
+            write_terms(&mut writer, terms)?;
+            let negative = clause.iter().filter(|&&lit| lit < 0).count() as i64;
+            writeln!(writer, " >= {}", 1 - negative)?;
+        }
+        writeln!(writer, "Binary")?;
+        write_terms(&mut writer, (1..=self.variables() as i64).map(|var| self.lp_name(var)))?;
+        writeln!(writer, "\nEnd")
+    }
+
+    /// Turns a satisfying assignment into moves, skipping empty steps and
+    /// moves that change nothing, and checks them with
+    /// `Grid::apply_solution`. `assignment[var]` is the value of `var`,
+    /// missing variables being false.
+    pub fn decode(&self, grid: &Grid, assignment: &[bool]) -> Result<Vec<u8>, String> {
+        let value = |var: i64| assignment.get(var as usize).copied().unwrap_or(false);
+        let graph = &self.graph;
+        let mut state = graph.initial_state();
+        let mut moves = Vec::new();
+        for t in 1..=self.steps {
+            let Some(color) = (0..graph.colors as u8).find(|&c| value(self.move_var(t, c))) else {
+                continue;
+            };
+            let next = graph.play(&state, color);
+            if graph.is_complete(&state) || next == state {
+                continue;
+            }
+            moves.push(color);
+            state = next;
+        }
+        if grid.clone().apply_solution(&moves) {
+            Ok(moves)
+        } else {
+            Err(format!("the assignment's {} moves do not flood the grid", moves.len()))
+        }
+    }
+}
+
+/// Writes LP terms, eight per line.
+fn write_terms<W: Write>(writer: &mut W, terms: impl Iterator<Item = String>) -> io::Result<()> {
+    for (i, term) in terms.enumerate() {
+        if i > 0 && i % 8 == 0 {
+            write!(writer, "\n   ")?;
+        }
+        write!(writer, " {}", term)?;
+    }
+    Ok(())
+}
+
+/// What a SAT solver answered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Indexed by variable, index 0 unused.
+    Satisfiable(Vec<bool>),
+    Unsatisfiable,
+    /// The solver gave up, e.g. on its own time limit.
+    Unknown,
+}
+
+/// Reads a solver's answer, either in the SAT competition format (`s
+/// SATISFIABLE` then `v` lines of literals) or in MiniSat's result file
+/// format (`SAT` then the literals).
+pub fn parse_outcome(text: &str) -> Result<Outcome, String> {
+    let mut status = None;
+    let mut literals = Vec::new();
+    for line in text.lines().map(str::trim) {
+        let line = line.strip_prefix("v ").unwrap_or(line);
+        match line.strip_prefix("s ").unwrap_or(line) {
+            "SATISFIABLE" | "SAT" => status = Some(true),
+            "UNSATISFIABLE" | "UNSAT" => status = Some(false),
+            "UNKNOWN" | "INDET" => return Ok(Outcome::Unknown),
+            _ if line.is_empty() || line.starts_with('c') || line.starts_with('s') => {}
+            _ => {
+                for token in line.split_whitespace() {
+                    literals.push(token.parse::<i64>().map_err(|_| format!("unexpected solver output {:?}", line))?);
+                }
+            }
+        }
+    }
+    match status {
+        Some(true) => {
+            let mut assignment = vec![false; literals.iter().map(|l| l.unsigned_abs() as usize + 1).max().unwrap_or(1)];
+            for lit in literals.into_iter().filter(|&lit| lit > 0) {
+                assignment[lit as usize] = true;
+            }
+            Ok(Outcome::Satisfiable(assignment))
+        }
+        Some(false) => Ok(Outcome::Unsatisfiable),
+        None => Err("the solver printed no SATISFIABLE or UNSATISFIABLE line".to_string()),
+    }
+}
+
+/// A SAT solver binary. In `args`, `{input}` stands for the CNF file and
+/// `{output}` for a result file the solver writes; without `{input}` the CNF
+/// file is passed last, and without `{output}` the answer is read from the
+/// solver's standard output.
+#[derive(Debug, Clone)]
+pub struct SatSolver {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl SatSolver {
+    pub fn new(program: &str) -> Self {
+        SatSolver { program: program.to_string(), args: Vec::new() }
+    }
+
+    /// Runs the solver on `cnf`, killing it if the budget runs out.
+    pub fn run(&self, cnf: &Path, budget: &Budget) -> Result<Outcome, String> {
+        let output = cnf.with_extension("out");
+        let stdout = cnf.with_extension("stdout");
+        let mut args: Vec<String> = self
+            .args
+            .iter()
+            .map(|arg| arg.replace("{input}", &cnf.to_string_lossy()).replace("{output}", &output.to_string_lossy()))
+            .collect();
+        if !self.args.iter().any(|arg| arg.contains("{input}")) {
+            args.push(cnf.to_string_lossy().into_owned());
+        }
+        let writes_output = self.args.iter().any(|arg| arg.contains("{output}"));
+
+        let result: Result<Outcome, String> = (|| {
+            let file = File::create(&stdout).map_err(|err| format!("cannot create {}: {}", stdout.display(), err))?;
+            let mut child = Command::new(&self.program)
+                .args(&args)
+                .stdout(Stdio::from(file))
+                .spawn()
+                .map_err(|err| format!("cannot run {}: {}", self.program, err))?;
+            let status = loop {
+                if let Some(status) = child.try_wait().map_err(|err| err.to_string())? {
+                    break status;
+                }
+                if budget.exhausted() {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Ok(Outcome::Unknown);
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            };
+            // Ctrl-C reaches the solver too, which may exit before being killed
+            if budget.exhausted() {
+                return Ok(Outcome::Unknown);
+            }
+            let answer = if writes_output { &output } else { &stdout };
+            let text = std::fs::read_to_string(answer).map_err(|err| format!("cannot read {}: {}", answer.display(), err))?;
+            parse_outcome(&text).map_err(|err| format!("{} ({}): {}", self.program, status, err))
+        })();
+        let _ = std::fs::remove_file(&output);
+        let _ = std::fs::remove_file(&stdout);
+        result
+    }
+}
+
+/// How the driver picks the next `k` to try.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchOrder {
+    /// Upwards from the lower bound: every call but the last is
+    /// unsatisfiable.
+    Linear,
+    /// Halves the interval between the lower bound and the best solution.
+    Binary,
+}
+
+impl SearchOrder {
+    pub const NAMES: [&'static str; 2] = ["binary", "linear"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(SearchOrder::Linear),
+            "binary" => Some(SearchOrder::Binary),
+            _ => None,
+        }
+    }
+}
+
+/// One solver call of the driver.
+#[derive(Debug, Clone)]
+pub struct Call {
+    pub steps: usize,
+    pub outcome: Outcome,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct SatSolution {
+    pub moves: Vec<u8>,
+    pub proven_optimal: bool,
+    pub calls: usize,
+}
+
+/// Finds a shortest solution by asking `solver` whether the grid can be
+/// flooded in `k` moves, for `k` between the lower bound and the greedy
+/// solution's length. Stops with the best solution found when the budget
+/// runs out or the solver answers unknown; `on_call` sees every answer.
+pub fn solve(grid: &Grid, solver: &SatSolver, order: SearchOrder, budget: &Budget, on_call: &mut dyn FnMut(&Call)) -> Result<SatSolution, String> {
+    let mut best = greedy::solve_greedy(grid, GreedyScore::Cells, budget);
+    let mut low = Bounds::of_grid(grid).value();
+    let mut calls = 0;
+    let dir = temp_dir();
+    std::fs::create_dir_all(&dir).map_err(|err| format!("cannot create {}: {}", dir.display(), err))?;
+
+    let result: Result<(), String> = (|| {
+        while low < best.len() && !budget.exhausted() {
+            let steps = match order {
+                SearchOrder::Linear => low,
+                SearchOrder::Binary => (low + best.len() - 1) / 2,
+            };
+            let encoding = Encoding::new(grid, steps);
+            let cnf = dir.join(format!("k{}.cnf", steps));
+            let file = File::create(&cnf).map_err(|err| format!("cannot create {}: {}", cnf.display(), err))?;
+            encoding.write_dimacs(io::BufWriter::new(file)).map_err(|err| format!("cannot write {}: {}", cnf.display(), err))?;
+
+            let start = Instant::now();
+            let outcome = solver.run(&cnf, budget)?;
+            calls += 1;
+            on_call(&Call { steps, outcome: outcome.clone(), elapsed: start.elapsed() });
+            match outcome {
+                Outcome::Satisfiable(assignment) => best = encoding.decode(grid, &assignment)?,
+                Outcome::Unsatisfiable => low = steps + 1,
+                Outcome::Unknown => break,
+            }
+        }
+        Ok(())
+    })();
+    let _ = std::fs::remove_dir_all(&dir);
+    result?;
+    Ok(SatSolution { proven_optimal: low >= best.len(), moves: best, calls })
+}
+
+/// Scratch directory for the CNF files of one `solve`.
+fn temp_dir() -> PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!("color-it-sat-{}-{}", std::process::id(), RUNS.fetch_add(1, Ordering::Relaxed)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::astar::solve_astar;
+    use crate::generate::{generate, Pattern};
+    use crate::table::DEFAULT_TABLE_BYTES;
+
+    const SAMPLE: &str = "1,2,0,0\n0,1,1,0\n2,2,0,1\n0,0,0,1";
+
+    fn stand_in() -> SatSolver {
+        let script = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/tiny-sat.awk");
+        SatSolver { program: "awk".to_string(), args: vec!["-f".to_string(), script.to_string()] }
+    }
+
+    /// The assignment playing `moves`, padded with empty steps.
+    fn assignment_of(encoding: &Encoding, moves: &[u8]) -> Vec<bool> {
+        let graph = &encoding.graph;
+        let mut assignment = vec![false; encoding.variables() + 1];
+        let mut state = graph.initial_state();
+        for t in 0..=encoding.steps {
+            if (1..=moves.len()).contains(&t) {
+                assignment[encoding.move_var(t, moves[t - 1]) as usize] = true;
+                state = graph.play(&state, moves[t - 1]);
+            }
+            for v in state.absorbed.iter() {
+                assignment[encoding.flooded_var(t, v) as usize] = true;
+            }
+        }
+        assignment
+    }
+
+    #[test]
+    fn test_optimal_solutions_satisfy_the_encoding() {
+        for seed in 0..4 {
+            let grid = generate(5, 5, 4, Pattern::Uniform, seed);
+            let optimal = solve_astar(&grid, DEFAULT_TABLE_BYTES, &Budget::unlimited(), &mut |_| {}).moves;
+            let encoding = Encoding::new(&grid, optimal.len() + 1);
+            let assignment = assignment_of(&encoding, &optimal);
+            for clause in encoding.clauses() {
+                assert!(clause.iter().any(|&lit| assignment[lit.unsigned_abs() as usize] == (lit > 0)), "seed {}: {:?}", seed, clause);
+            }
+            assert_eq!(encoding.decode(&grid, &assignment).unwrap(), optimal);
+        }
+    }
+
+    #[test]
+    fn test_decode_rejects_incomplete_assignments() {
+        let grid = Grid::from_csv(SAMPLE).unwrap();
+        let encoding = Encoding::new(&grid, 4);
+        assert!(encoding.decode(&grid, &assignment_of(&encoding, &[2, 1])).is_err());
+    }
+
+    #[test]
+    fn test_parse_outcome() {
+        let competition = "c comment\ns SATISFIABLE\nv 1 -2\nv 3 0\n";
+        assert_eq!(parse_outcome(competition).unwrap(), Outcome::Satisfiable(vec![false, true, false, true]));
+        assert_eq!(parse_outcome("SAT\n-1 2 0\n").unwrap(), Outcome::Satisfiable(vec![false, false, true]));
+        assert_eq!(parse_outcome("s UNSATISFIABLE\n").unwrap(), Outcome::Unsatisfiable);
+        assert_eq!(parse_outcome("UNSAT\n").unwrap(), Outcome::Unsatisfiable);
+        assert_eq!(parse_outcome("s UNKNOWN\n").unwrap(), Outcome::Unknown);
+        assert!(parse_outcome("segmentation fault\n").is_err());
+    }
+
+    #[test]
+    fn test_exports() {
+        let grid = Grid::from_csv(SAMPLE).unwrap();
+        let encoding = Encoding::new(&grid, 4);
+        let mut cnf = Vec::new();
+        encoding.write_dimacs(&mut cnf).unwrap();
+        let cnf = String::from_utf8(cnf).unwrap();
+        assert!(cnf.contains(&format!("p cnf {} {}\n", encoding.variables(), encoding.clauses().len())));
+
+        let mut lp = Vec::new();
+        encoding.write_lp(&mut lp).unwrap();
+        let lp = String::from_utf8(lp).unwrap();
+        assert!(lp.starts_with("\\ Flood-It in at most 4 moves\nMinimize\n moves: m1_0 + m1_1 + m1_2"));
+        assert!(lp.contains(" c1: a0_0 >= 1\n"));
+        assert!(lp.trim_end().ends_with("End"));
+    }
+
+    #[test]
+    fn test_driver_with_a_stand_in_solver() {
+        let grid = Grid::from_csv(SAMPLE).unwrap();
+        for order in [SearchOrder::Linear, SearchOrder::Binary] {
+            let mut steps = Vec::new();
+            let solution = solve(&grid, &stand_in(), order, &Budget::unlimited(), &mut |call| steps.push(call.steps)).unwrap();
+            assert_eq!(solution.moves.len(), 4);
+            assert!(solution.proven_optimal);
+            assert!(grid.is_solution(&solution.moves));
+            assert_eq!(solution.calls, steps.len());
+        }
+
+        let grid = generate(5, 5, 4, Pattern::Uniform, 3);
+        let optimal = solve_astar(&grid, DEFAULT_TABLE_BYTES, &Budget::unlimited(), &mut |_| {}).moves;
+        let solution = solve(&grid, &stand_in(), SearchOrder::Binary, &Budget::unlimited(), &mut |_| {}).unwrap();
+        assert!(solution.proven_optimal);
+        assert_eq!(solution.moves.len(), optimal.len());
+    }
+}
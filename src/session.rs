@@ -0,0 +1,126 @@
+//! A game played by hand: the moves so far, undo and redo, and hints from a
+//! solver.
+
+use crate::flood::Flood;
+use crate::solver::{Solver, SolverOptions};
+use crate::Grid;
+
+#[derive(Debug, Clone)]
+pub struct Session {
+    grid: Grid,
+    flood: Flood,
+    moves: Vec<u8>,
+    /// Undone moves, the most recent last.
+    undone: Vec<u8>,
+}
+
+impl Session {
+    pub fn new(grid: Grid) -> Self {
+        let flood = Flood::new(&grid);
+        Session { grid, flood, moves: Vec::new(), undone: Vec::new() }
+    }
+
+    /// The grid as it started.
+    pub fn initial(&self) -> &Grid {
+        &self.grid
+    }
+
+    /// The grid after the moves played so far.
+    pub fn current(&self) -> Grid {
+        self.flood.to_grid()
+    }
+
+    pub fn moves(&self) -> &[u8] {
+        &self.moves
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.flood.is_complete()
+    }
+
+    /// Plays `color` and returns how many cells it absorbed. Moves that use
+    /// a colour the grid lacks or change nothing are refused. Playing drops
+    /// the undone moves.
+    pub fn play(&mut self, color: u8) -> Result<usize, String> {
+        if color as usize >= self.grid.colors() {
+            return Err(format!("the grid has no colour {}", color));
+        }
+        if self.flood.is_noop(color) {
+            return Err(format!("the region is already colour {}", color));
+        }
+        self.undone.clear();
+        self.moves.push(color);
+        Ok(self.flood.play(color))
+    }
+
+    /// Takes back the last move, returning it.
+    pub fn undo(&mut self) -> Option<u8> {
+        let color = self.moves.pop()?;
+        self.undone.push(color);
+        self.flood = Flood::new(&self.grid);
+        for &color in &self.moves {
+            self.flood.play(color);
+        }
+        Some(color)
+    }
+
+    /// Replays the last undone move, returning it.
+    pub fn redo(&mut self) -> Option<u8> {
+        let color = self.undone.pop()?;
+        self.moves.push(color);
+        self.flood.play(color);
+        Some(color)
+    }
+
+    /// Next move of the solution `solver` finds from the current position,
+    /// and how many moves that solution has. `None` once the grid is flooded.
+    pub fn hint(&self, solver: &dyn Solver, options: &SolverOptions) -> Option<(u8, usize)> {
+        if self.is_complete() {
+            return None;
+        }
+        let solution = solver.solve(&self.current(), options, &mut |_| {});
+        solution.moves.first().map(|&color| (color, solution.moves.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Registry;
+
+    const SAMPLE: &str = "1,2,0,0\n0,1,1,0\n2,2,0,1\n0,0,0,1";
+
+    #[test]
+    fn test_undo_and_redo() {
+        let mut session = Session::new(Grid::from_csv(SAMPLE).unwrap());
+        assert_eq!(session.play(2), Ok(1));
+        assert!(session.play(2).is_err());
+        assert!(session.play(3).is_err());
+        session.play(1).unwrap();
+        assert_eq!(session.undo(), Some(1));
+        assert_eq!(session.current().to_csv(), "2,2,0,0\n0,1,1,0\n2,2,0,1\n0,0,0,1\n");
+        assert_eq!(session.redo(), Some(1));
+        assert_eq!(session.redo(), None);
+        session.undo();
+        session.play(0).unwrap();
+        assert_eq!(session.redo(), None);
+        assert_eq!(session.moves(), [2, 0]);
+        assert_eq!(session.undo(), Some(0));
+        assert_eq!(session.undo(), Some(2));
+        assert_eq!(session.undo(), None);
+    }
+
+    #[test]
+    fn test_hints_lead_to_an_optimal_solution() {
+        let registry = Registry::default();
+        let solver = registry.get("astar").unwrap();
+        let options = SolverOptions::default();
+        let mut session = Session::new(Grid::from_csv(SAMPLE).unwrap());
+        while let Some((color, remaining)) = session.hint(solver, &options) {
+            assert_eq!(session.moves().len() + remaining, 4);
+            session.play(color).unwrap();
+        }
+        assert!(session.is_complete());
+        assert!(session.initial().is_solution(session.moves()));
+    }
+}
@@ -0,0 +1,70 @@
+//! The solution file format: one colour per line, in the order they are played.
+//! Free-Flood-It solutions give `row,col,colour` on each line instead.
+
+use crate::free::FreeMove;
+
+pub fn to_string(moves: &[u8]) -> String {
+    let mut output = String::new();
+    for &m in moves {
+        output.push_str(&m.to_string());
+        output.push('\n');
+    }
+    output
+}
+
+/// Parses a solution file. Blank lines are ignored.
+pub fn parse(content: &str) -> Option<Vec<u8>> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| line.parse::<u8>().ok())
+        .collect()
+}
+
+pub fn free_to_string(moves: &[FreeMove]) -> String {
+    moves.iter().map(|m| format!("{}\n", m)).collect()
+}
+
+/// Whether a solution file holds Free-Flood-It moves.
+pub fn is_free(content: &str) -> bool {
+    content.contains(',')
+}
+
+/// Parses a Free-Flood-It solution file. Blank lines are ignored.
+pub fn parse_free(content: &str) -> Option<Vec<FreeMove>> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let mut fields = line.split(',').map(str::trim);
+            let m = FreeMove { row: fields.next()?.parse().ok()?, col: fields.next()?.parse().ok()?, color: fields.next()?.parse().ok()? };
+            fields.next().is_none().then_some(m)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let moves = vec![0, 2, 0, 1];
+        assert_eq!(to_string(&moves), "0\n2\n0\n1\n");
+        assert_eq!(parse(&to_string(&moves)), Some(moves));
+        assert_eq!(parse("1\nx\n"), None);
+    }
+
+    #[test]
+    fn test_free_round_trip() {
+        let moves = vec![FreeMove { row: 2, col: 0, color: 1 }, FreeMove { row: 0, col: 13, color: 4 }];
+        assert_eq!(free_to_string(&moves), "2,0,1\n0,13,4\n");
+        assert!(is_free(&free_to_string(&moves)) && !is_free("1\n2\n"));
+        assert_eq!(parse_free(&free_to_string(&moves)), Some(moves));
+        assert_eq!(parse_free("1,2\n"), None);
+        assert_eq!(parse_free("1,2,3,4\n"), None);
+        assert_eq!(parse_free("1,2,300\n"), None);
+    }
+}
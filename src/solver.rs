@@ -0,0 +1,401 @@
+use std::time::{Duration, Instant};
+
+use crate::astar::{self, SearchResult};
+use crate::bound::Bounds;
+use crate::budget::{Budget, Limits, StopReason};
+use crate::dfs;
+use crate::greedy::{self, GreedyScore};
+use crate::montecarlo::{self, Method, MonteCarloOptions};
+use crate::parallel;
+use crate::table::{TableStats, DEFAULT_TABLE_BYTES};
+use crate::Grid;
+
+/// Tuning knobs shared by every strategy. Each solver reads the ones it needs.
+#[derive(Debug, Clone)]
+pub struct SolverOptions {
+    /// Memory cap of the transposition table, in bytes.
+    pub table_bytes: usize,
+    /// Depth of the lookahead strategy.
+    pub lookahead: usize,
+    /// Number of states kept per depth by beam search.
+    pub beam_width: usize,
+    /// Seed, iterations and rollouts of the Monte Carlo strategies.
+    pub monte_carlo: MonteCarloOptions,
+    /// Time, node and memory limits after which the best solution so far is returned.
+    pub limits: Limits,
+}
+
+impl Default for SolverOptions {
+    fn default() -> Self {
+        SolverOptions {
+            table_bytes: DEFAULT_TABLE_BYTES,
+            lookahead: 2,
+            beam_width: 16,
+            monte_carlo: MonteCarloOptions::default(),
+            limits: Limits::default(),
+        }
+    }
+}
+
+/// Reported to the progress callback whenever a solver has a new best solution.
+#[derive(Debug, Clone, Copy)]
+pub struct Progress<'a> {
+    pub moves: &'a [u8],
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct Solution {
+    pub moves: Vec<u8>,
+    pub proven_optimal: bool,
+    /// Set when a limit or an interrupt cut the search short.
+    pub stopped: Option<StopReason>,
+    pub nodes_expanded: usize,
+    pub elapsed: Duration,
+    /// Transposition table usage, for solvers that keep one.
+    pub table: Option<TableStats>,
+}
+
+pub trait Solver: Send + Sync {
+    /// Name used by `--strategy` and the registry.
+    fn name(&self) -> &'static str;
+
+    fn description(&self) -> &str;
+
+    fn solve(&self, grid: &Grid, options: &SolverOptions, progress: &mut dyn FnMut(Progress)) -> Solution;
+}
+
+/// Solver backed by a plain function. This is how the built-in strategies are
+/// registered, and the easiest way to add one.
+pub struct FnSolver<F> {
+    name: &'static str,
+    description: &'static str,
+    solve: F,
+}
+
+impl<F> FnSolver<F>
+where
+    F: Fn(&Grid, &SolverOptions, &Budget, &mut dyn FnMut(&[u8])) -> SearchResult + Send + Sync,
+{
+    pub fn new(name: &'static str, description: &'static str, solve: F) -> Self {
+        FnSolver { name, description, solve }
+    }
+}
+
+impl<F> Solver for FnSolver<F>
+where
+    F: Fn(&Grid, &SolverOptions, &Budget, &mut dyn FnMut(&[u8])) -> SearchResult + Send + Sync,
+{
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn description(&self) -> &str {
+        self.description
+    }
+
+    fn solve(&self, grid: &Grid, options: &SolverOptions, progress: &mut dyn FnMut(Progress)) -> Solution {
+        let start = Instant::now();
+        let budget = Budget::new(&options.limits);
+        let mut reported = None;
+        let result = (self.solve)(grid, options, &budget, &mut |moves| {
+            reported = Some(moves.len());
+            progress(Progress { moves, elapsed: start.elapsed() });
+        });
+        if reported != Some(result.moves.len()) {
+            progress(Progress { moves: &result.moves, elapsed: start.elapsed() });
+        }
+
+        // A solution as short as the lower bound is optimal however it was found
+        let stopped = budget.stop_reason();
+        let certified = result.moves.len() <= Bounds::of_grid(grid).value();
+        Solution {
+            moves: result.moves,
+            proven_optimal: (result.proven_optimal && stopped.is_none()) || certified,
+            stopped,
+            nodes_expanded: result.nodes_expanded,
+            elapsed: start.elapsed(),
+            table: (result.table != TableStats::default()).then_some(result.table),
+        }
+    }
+}
+
+fn heuristic(moves: Vec<u8>) -> SearchResult {
+    SearchResult { moves, proven_optimal: false, nodes_expanded: 0, table: TableStats::default() }
+}
+
+/// Strategies selectable by name, in registration order.
+pub struct Registry {
+    solvers: Vec<Box<dyn Solver>>,
+}
+
+impl Registry {
+    pub fn empty() -> Self {
+        Registry { solvers: Vec::new() }
+    }
+
+    /// Adds `solver`, replacing any solver already registered under its name.
+    pub fn register(&mut self, solver: Box<dyn Solver>) {
+        match self.solvers.iter_mut().find(|s| s.name() == solver.name()) {
+            Some(existing) => *existing = solver,
+            None => self.solvers.push(solver),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Solver> {
+        self.solvers.iter().find(|s| s.name() == name).map(|s| s.as_ref())
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.solvers.iter().map(|s| s.name()).collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Solver> {
+        self.solvers.iter().map(|s| s.as_ref())
+    }
+}
+
+impl Default for Registry {
+    /// The built-in strategies, with the original DFS first.
+    fn default() -> Self {
+        let mut registry = Registry::empty();
+        registry.register(Box::new(FnSolver::new(
+            "dfs",
+            "depth-first search that skips states already seen",
+            |grid, options, budget, on_improve| {
+                dfs::solve_dfs(grid, budget.table_bytes(options.table_bytes), budget, on_improve)
+            },
+        )));
+        registry.register(Box::new(FnSolver::new(
+            "astar",
+            "optimal A* search",
+            |grid, options, budget, on_improve| {
+                astar::solve_astar(grid, budget.table_bytes(options.table_bytes), budget, on_improve)
+            },
+        )));
+        registry.register(Box::new(FnSolver::new(
+            "ida",
+            "optimal iterative deepening A*",
+            |grid, options, budget, on_improve| {
+                astar::solve_ida(grid, budget.table_bytes(options.table_bytes), budget, on_improve)
+            },
+        )));
+        registry.register(Box::new(FnSolver::new(
+            "parallel",
+            "optimal branch and bound over first moves in parallel",
+            |grid, options, budget, on_improve| {
+                parallel::solve_branch_and_bound(grid, budget.table_bytes(options.table_bytes), budget, on_improve)
+            },
+        )));
+        registry.register(Box::new(FnSolver::new(
+            "greedy",
+            "play the move absorbing the most cells",
+            |grid, _, budget, _| heuristic(greedy::solve_greedy(grid, GreedyScore::Cells, budget)),
+        )));
+        registry.register(Box::new(FnSolver::new(
+            "greedy-colors",
+            "play the move eliminating the most colours",
+            |grid, _, budget, _| heuristic(greedy::solve_greedy(grid, GreedyScore::ColorClasses, budget)),
+        )));
+        registry.register(Box::new(FnSolver::new(
+            "lookahead",
+            "greedy judged over the next `lookahead` moves",
+            |grid, options, budget, _| heuristic(greedy::solve_lookahead(grid, options.lookahead, budget)),
+        )));
+        registry.register(Box::new(FnSolver::new(
+            "beam",
+            "beam search keeping `beam-width` states per depth",
+            |grid, options, budget, _| heuristic(greedy::solve_beam(grid, options.beam_width, budget)),
+        )));
+        registry.register(Box::new(FnSolver::new(
+            "portfolio",
+            "run the heuristics concurrently and keep the best",
+            |grid, options, budget, _| {
+                heuristic(parallel::solve_portfolio(grid, options.lookahead, options.beam_width, budget).1)
+            },
+        )));
+        registry.register(Box::new(FnSolver::new(
+            "mcts",
+            "Monte Carlo tree search over move sequences",
+            |grid, options, budget, _| montecarlo::solve(grid, Method::Mcts, &options.monte_carlo, budget),
+        )));
+        registry.register(Box::new(FnSolver::new(
+            "nmcs",
+            "nested Monte Carlo search",
+            |grid, options, budget, _| montecarlo::solve(grid, Method::Nmcs, &options.monte_carlo, budget),
+        )));
+        registry.register(Box::new(FnSolver::new(
+            "nrpa",
+            "nested rollout policy adaptation",
+            |grid, options, budget, _| montecarlo::solve(grid, Method::Nrpa, &options.monte_carlo, budget),
+        )));
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::generate::{generate, generate_on, Pattern};
+    use crate::Topology;
+
+    #[test]
+    fn test_every_registered_solver_solves_the_sample() {
+        let grid = Grid::from_csv("1,2,0,0\n0,1,1,0\n2,2,0,1\n0,0,0,1").unwrap();
+        let options = SolverOptions::default();
+        for solver in Registry::default().iter() {
+            let mut reports = 0;
+            let solution = solver.solve(&grid, &options, &mut |_| reports += 1);
+            assert!(grid.clone().apply_solution(&solution.moves), "{} failed", solver.name());
+            assert!(reports > 0, "{} never reported progress", solver.name());
+            if solution.proven_optimal {
+                assert_eq!(solution.moves.len(), 4, "{} is not optimal", solver.name());
+            }
+        }
+    }
+
+    #[test]
+    fn test_optimal_solvers_agree_on_generated_grids() {
+        let registry = Registry::default();
+        let options = SolverOptions::default();
+        for seed in 0..4 {
+            for pattern in [Pattern::Uniform, Pattern::Clusters { patch_size: 4 }] {
+                let grid = generate(6, 6, 4, pattern, seed);
+                let lengths: Vec<usize> = ["astar", "ida", "parallel"]
+                    .iter()
+                    .map(|name| registry.get(name).unwrap().solve(&grid, &options, &mut |_| {}))
+                    .inspect(|solution| assert!(grid.is_solution(&solution.moves)))
+                    .map(|solution| solution.moves.len())
+                    .collect();
+                assert!(lengths.windows(2).all(|w| w[0] == w[1]), "seed {} {}: {:?}", seed, pattern, lengths);
+            }
+        }
+    }
+
+    #[test]
+    fn test_optimal_solvers_agree_on_every_topology() {
+        let registry = Registry::default();
+        let options = SolverOptions::default();
+        for name in Topology::NAMES {
+            let grid = generate_on(name.parse().unwrap(), 6, 5, 4, Pattern::Uniform, 11);
+            let reference = graph_search_length(&grid);
+            for solver in registry.iter() {
+                let solution = solver.solve(&grid, &options, &mut |_| {});
+                assert!(grid.is_solution(&solution.moves), "{} failed on {}", solver.name(), name);
+                if solution.proven_optimal {
+                    assert_eq!(solution.moves.len(), reference, "{} on {}", solver.name(), name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_optimal_solvers_agree_with_several_origins() {
+        let registry = Registry::default();
+        let options = SolverOptions::default();
+        for (seed, origins) in [(0, vec![(2, 2)]), (1, vec![(0, 0), (5, 4)]), (2, vec![(0, 0), (1, 0), (4, 3)])] {
+            let mut grid = generate(6, 5, 4, Pattern::Uniform, seed);
+            grid.set_origins(&origins);
+            let reference = graph_search_length(&grid);
+            for solver in registry.iter() {
+                let solution = solver.solve(&grid, &options, &mut |_| {});
+                assert!(grid.is_solution(&solution.moves), "{} failed on {:?}", solver.name(), origins);
+                if solution.proven_optimal {
+                    assert_eq!(solution.moves.len(), reference, "{} on {:?}", solver.name(), origins);
+                }
+            }
+        }
+    }
+
+    /// Optimal length by breadth-first search over grids replayed with
+    /// `flood_fill`, independent of the region graph.
+    fn graph_search_length(grid: &Grid) -> usize {
+        let mut frontier = vec![grid.clone()];
+        for depth in 0.. {
+            if frontier.iter().any(Grid::is_complete) {
+                return depth;
+            }
+            let mut seen = std::collections::HashSet::new();
+            frontier = frontier
+                .iter()
+                .flat_map(|g| (0..grid.colors() as u8).filter(move |&c| g.origins().iter().any(|&(x, y)| g.get(x, y) != c)).map(move |c| (g, c)))
+                .map(|(g, c)| {
+                    let mut next = g.clone();
+                    next.flood_fill(c);
+                    next
+                })
+                .filter(|g| seen.insert(g.to_csv()))
+                .collect();
+        }
+        unreachable!()
+    }
+
+    #[test]
+    fn test_every_solver_respects_walls() {
+        // 1 2 # 0
+        // 0 # 1 0
+        // 2 2 0 #
+        // # 0 0 1
+        let grid = Grid::from_csv("1,2,#,0\n0,#,1,0\n2,2,0,#\n#,0,0,1").unwrap();
+        let options = SolverOptions::default();
+        for solver in Registry::default().iter() {
+            let solution = solver.solve(&grid, &options, &mut |_| {});
+            assert!(grid.is_solution(&solution.moves), "{} failed", solver.name());
+            if solution.proven_optimal {
+                assert_eq!(solution.moves.len(), 5, "{} is not optimal", solver.name());
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_solver_can_be_registered() {
+        let mut registry = Registry::default();
+        registry.register(Box::new(FnSolver::new("first-color", "always play colour 0 then 1", |_, _, _, _| {
+            heuristic(vec![0, 1])
+        })));
+        assert_eq!(registry.names().last(), Some(&"first-color"));
+
+        let grid = Grid::from_csv("0,1\n1,1").unwrap();
+        let solver = registry.get("first-color").unwrap();
+        let solution = solver.solve(&grid, &SolverOptions::default(), &mut |_| {});
+        assert!(grid.clone().apply_solution(&solution.moves));
+    }
+
+    #[test]
+    fn test_solutions_at_the_lower_bound_are_optimal() {
+        let greedy = Registry::default();
+        let greedy = greedy.get("greedy").unwrap();
+        let sample = Grid::from_csv("1,2,0,0\n0,1,1,0\n2,2,0,1\n0,0,0,1").unwrap();
+        assert!(greedy.solve(&sample, &SolverOptions::default(), &mut |_| {}).proven_optimal);
+        let grid = Grid::from_csv("2,3,4,2,2,3\n4,2,1,3,2,3\n2,2,2,0,3,4\n3,4,0,0,2,0\n1,0,2,3,0,4\n2,2,1,2,1,2").unwrap();
+        assert!(!greedy.solve(&grid, &SolverOptions::default(), &mut |_| {}).proven_optimal);
+    }
+
+    #[test]
+    fn test_limits_return_the_incumbent() {
+        let grid = Grid::from_csv("2,3,4,2,2,3\n4,2,1,3,2,3\n2,2,2,0,3,4\n3,4,0,0,2,0\n1,0,2,3,0,4\n2,2,1,2,1,2").unwrap();
+        let limits = Limits { nodes: Some(3), ..Limits::default() };
+        let options = SolverOptions { limits, ..SolverOptions::default() };
+        for name in ["dfs", "astar", "ida", "parallel"] {
+            let solution = Registry::default().get(name).unwrap().solve(&grid, &options, &mut |_| {});
+            assert!(grid.clone().apply_solution(&solution.moves), "{} failed", name);
+            assert!(!solution.proven_optimal, "{} claims optimality", name);
+            assert_eq!(solution.stopped, Some(StopReason::NodeLimit));
+        }
+    }
+
+    #[test]
+    fn test_greedy_stops_when_interrupted() {
+        let grid = Grid::from_csv("2,1,3,0,4\n1,2,2,3,1\n0,3,1,2,4\n4,1,0,3,2\n3,2,4,1,0").unwrap();
+        let limits = Limits { stop: Some(Arc::new(AtomicBool::new(true))), ..Limits::default() };
+        let options = SolverOptions { limits, ..SolverOptions::default() };
+        for name in ["greedy", "greedy-colors"] {
+            let solution = Registry::default().get(name).unwrap().solve(&grid, &options, &mut |_| {});
+            assert!(grid.clone().apply_solution(&solution.moves), "{} failed", name);
+            assert_eq!(solution.stopped, Some(StopReason::Interrupted));
+        }
+    }
+}
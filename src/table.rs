@@ -0,0 +1,222 @@
+use std::fmt;
+
+use crate::region::{BitSet, FloodState};
+
+/// Default memory cap for a transposition table.
+pub const DEFAULT_TABLE_BYTES: usize = 64 << 20;
+
+const INITIAL_BUCKETS: usize = 1 << 10;
+
+/// Stored key of a state: its frontier is derived data and is left out.
+#[derive(Debug, Clone)]
+struct Entry {
+    absorbed: BitSet,
+    color: u8,
+    hash: u64,
+    depth: usize,
+}
+
+impl Entry {
+    fn is_key(&self, hash: u64, color: u8, absorbed: &BitSet) -> bool {
+        self.hash == hash && self.color == color && self.absorbed == *absorbed
+    }
+}
+
+/// Counters reported alongside search results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TableStats {
+    pub probes: usize,
+    pub hits: usize,
+    pub stores: usize,
+    pub replacements: usize,
+    pub entries: usize,
+    pub bytes: usize,
+}
+
+impl TableStats {
+    pub fn hit_rate(&self) -> f64 {
+        if self.probes == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.probes as f64
+        }
+    }
+
+    pub fn merge(&mut self, other: &TableStats) {
+        self.probes += other.probes;
+        self.hits += other.hits;
+        self.stores += other.stores;
+        self.replacements += other.replacements;
+        self.entries += other.entries;
+        self.bytes += other.bytes;
+    }
+}
+
+impl fmt::Display for TableStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} entries, {:.1} KiB, hit rate {:.1}% ({} probes), {} replacements",
+            self.entries,
+            self.bytes as f64 / 1024.0,
+            100.0 * self.hit_rate(),
+            self.probes,
+            self.replacements,
+        )
+    }
+}
+
+/// Bounded map from flood states to the shallowest depth they were reached at.
+///
+/// States are located by their Zobrist hash and verified against the stored
+/// bitset, so collisions never produce false hits. Each bucket has a
+/// depth-preferred slot, which keeps the shallowest entry, and an
+/// always-replace slot for the most recent one. The table doubles as it fills
+/// up until it reaches the memory cap.
+#[derive(Debug)]
+pub struct TranspositionTable {
+    buckets: Vec<[Option<Entry>; 2]>,
+    max_buckets: usize,
+    key_bytes: usize,
+    stats: TableStats,
+}
+
+impl TranspositionTable {
+    /// `components` is the size of the region graph, which fixes the size of
+    /// every stored bitset.
+    pub fn new(max_bytes: usize, components: usize) -> Self {
+        let key_bytes = components.div_ceil(64) * 8;
+        let entry_bytes = std::mem::size_of::<Option<Entry>>() + key_bytes;
+        let max_buckets = (max_bytes / (2 * entry_bytes)).max(1);
+        let max_buckets = if max_buckets.is_power_of_two() {
+            max_buckets
+        } else {
+            max_buckets.next_power_of_two() / 2
+        };
+        TranspositionTable {
+            buckets: vec![[None, None]; INITIAL_BUCKETS.min(max_buckets)],
+            max_buckets,
+            key_bytes,
+            stats: TableStats::default(),
+        }
+    }
+
+    fn bucket(&self, hash: u64) -> usize {
+        (hash as usize) & (self.buckets.len() - 1)
+    }
+
+    /// Depth recorded for `state`, if it is still in the table.
+    pub fn probe(&mut self, state: &FloodState) -> Option<usize> {
+        self.stats.probes += 1;
+        let found = self.buckets[self.bucket(state.hash)]
+            .iter()
+            .flatten()
+            .find(|entry| entry.is_key(state.hash, state.color, &state.absorbed))
+            .map(|entry| entry.depth);
+        if found.is_some() {
+            self.stats.hits += 1;
+        }
+        found
+    }
+
+    /// Records that `state` was reached at `depth`, keeping the shallower
+    /// depth if it is already present.
+    pub fn store(&mut self, state: FloodState, depth: usize) {
+        if 2 * self.stats.entries >= self.buckets.len() && self.buckets.len() < self.max_buckets {
+            self.grow();
+        }
+        self.stats.stores += 1;
+        self.insert(Entry { absorbed: state.absorbed, color: state.color, hash: state.hash, depth });
+    }
+
+    fn insert(&mut self, entry: Entry) {
+        let index = self.bucket(entry.hash);
+        let bucket = &mut self.buckets[index];
+
+        if let Some(existing) =
+            bucket.iter_mut().flatten().find(|e| e.is_key(entry.hash, entry.color, &entry.absorbed))
+        {
+            existing.depth = existing.depth.min(entry.depth);
+            return;
+        }
+
+        let evicted = match &bucket[0] {
+            None => bucket[0].replace(entry),
+            Some(preferred) if entry.depth <= preferred.depth => {
+                // Demote the old preferred entry rather than losing it outright.
+                let demoted = bucket[0].replace(entry);
+                std::mem::replace(&mut bucket[1], demoted)
+            }
+            Some(_) => bucket[1].replace(entry),
+        };
+        match evicted {
+            Some(_) => self.stats.replacements += 1,
+            None => self.stats.entries += 1,
+        }
+    }
+
+    fn grow(&mut self) {
+        let doubled = vec![[None, None]; self.buckets.len() * 2];
+        let old = std::mem::replace(&mut self.buckets, doubled);
+        self.stats.entries = 0;
+        for entry in old.into_iter().flatten().flatten() {
+            self.insert(entry);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for bucket in &mut self.buckets {
+            *bucket = [None, None];
+        }
+        self.stats.entries = 0;
+    }
+
+    pub fn stats(&self) -> TableStats {
+        let slots = self.buckets.len() * 2 * std::mem::size_of::<Option<Entry>>();
+        TableStats { bytes: slots + self.stats.entries * self.key_bytes, ..self.stats }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region::RegionGraph;
+    use crate::Grid;
+
+    fn states() -> (RegionGraph, Vec<FloodState>) {
+        let grid = Grid::from_csv("2,1,3,0,4\n1,2,2,3,1\n0,3,1,2,4\n4,1,0,3,2\n3,2,4,1,0").unwrap();
+        let graph = RegionGraph::new(&grid);
+        let mut states = vec![graph.initial_state()];
+        for i in 0..40 {
+            let from = &states[i / 4];
+            let next = graph.play(from, (i % 5) as u8);
+            states.push(next);
+        }
+        (graph, states)
+    }
+
+    #[test]
+    fn test_probe_after_store() {
+        let (graph, states) = states();
+        let mut table = TranspositionTable::new(DEFAULT_TABLE_BYTES, graph.component_count());
+        table.store(states[1].clone(), 3);
+        table.store(states[1].clone(), 1);
+        assert_eq!(table.probe(&states[1]), Some(1));
+        let stats = table.stats();
+        assert_eq!((stats.probes, stats.hits, stats.entries), (1, 1, 1));
+    }
+
+    #[test]
+    fn test_memory_cap_is_respected() {
+        let (graph, states) = states();
+        let mut table = TranspositionTable::new(1, graph.component_count());
+        for (depth, state) in states.iter().enumerate() {
+            table.store(state.clone(), depth);
+        }
+        let stats = table.stats();
+        assert!(stats.entries <= 2);
+        assert!(stats.replacements > 0);
+        // The depth-preferred slot keeps the root.
+        assert_eq!(table.probe(&states[0]), Some(0));
+    }
+}
@@ -0,0 +1,132 @@
+//! Which cells of a grid touch each other.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Adjacency between the cells of a grid. Cells are addressed by column `x`
+/// and row `y` whatever the topology.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Topology {
+    /// Four orthogonal neighbours.
+    #[default]
+    Square,
+    /// Orthogonal and diagonal neighbours.
+    Square8,
+    /// Pointy-top hexagons in "odd-r" offset layout: every odd row (the
+    /// second, fourth, ... of the file) is shifted half a cell to the right.
+    Hex,
+    /// Pointy-top hexagons in axial coordinates: column `q`, row `r`. Row `r`
+    /// is shifted `r` half cells to the right, so the board is a rhombus; use
+    /// walls to carve other shapes.
+    HexAxial,
+    /// Four orthogonal neighbours, with each edge wrapping to the opposite one.
+    Torus,
+}
+
+const SQUARE: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const SQUARE8: [(isize, isize); 8] = [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, -1), (-1, 1), (1, 1)];
+const HEX_EVEN_ROW: [(isize, isize); 6] = [(-1, 0), (1, 0), (-1, -1), (0, -1), (-1, 1), (0, 1)];
+const HEX_ODD_ROW: [(isize, isize); 6] = [(-1, 0), (1, 0), (0, -1), (1, -1), (0, 1), (1, 1)];
+const HEX_AXIAL: [(isize, isize); 6] = [(-1, 0), (1, 0), (0, -1), (1, -1), (-1, 1), (0, 1)];
+
+impl Topology {
+    /// Names accepted by `FromStr`, in declaration order.
+    pub const NAMES: [&'static str; 5] = ["square", "square8", "hex", "hex-axial", "torus"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Topology::Square => "square",
+            Topology::Square8 => "square8",
+            Topology::Hex => "hex",
+            Topology::HexAxial => "hex-axial",
+            Topology::Torus => "torus",
+        }
+    }
+
+    /// Cells touching `(x, y)` on a `width` x `height` board. On a torus
+    /// narrower than three cells the same neighbour can come up twice.
+    pub fn neighbours(self, x: usize, y: usize, width: usize, height: usize) -> impl Iterator<Item = (usize, usize)> {
+        let offsets: &'static [(isize, isize)] = match self {
+            Topology::Square | Topology::Torus => &SQUARE,
+            Topology::Square8 => &SQUARE8,
+            Topology::Hex if y.is_multiple_of(2) => &HEX_EVEN_ROW,
+            Topology::Hex => &HEX_ODD_ROW,
+            Topology::HexAxial => &HEX_AXIAL,
+        };
+        let wrap = self == Topology::Torus;
+        offsets.iter().filter_map(move |&(dx, dy)| {
+            let (nx, ny) = (x as isize + dx, y as isize + dy);
+            if wrap {
+                Some((nx.rem_euclid(width as isize) as usize, ny.rem_euclid(height as isize) as usize))
+            } else if (0..width as isize).contains(&nx) && (0..height as isize).contains(&ny) {
+                Some((nx as usize, ny as usize))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Half cells by which row `y` is shifted right when printed.
+    pub fn row_shift(self, y: usize) -> usize {
+        match self {
+            Topology::Hex => y % 2,
+            Topology::HexAxial => y,
+            _ => 0,
+        }
+    }
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "square" => Ok(Topology::Square),
+            "square8" => Ok(Topology::Square8),
+            "hex" => Ok(Topology::Hex),
+            "hex-axial" => Ok(Topology::HexAxial),
+            "torus" => Ok(Topology::Torus),
+            _ => Err(format!("unknown topology {:?}, expected one of {}", s, Topology::NAMES.join(", "))),
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(topology: Topology, x: usize, y: usize) -> Vec<(usize, usize)> {
+        let mut cells: Vec<_> = topology.neighbours(x, y, 4, 4).collect();
+        cells.sort_unstable();
+        cells
+    }
+
+    #[test]
+    fn test_neighbours() {
+        assert_eq!(sorted(Topology::Square, 0, 0), vec![(0, 1), (1, 0)]);
+        assert_eq!(sorted(Topology::Square8, 0, 0), vec![(0, 1), (1, 0), (1, 1)]);
+        assert_eq!(sorted(Topology::Torus, 0, 0), vec![(0, 1), (0, 3), (1, 0), (3, 0)]);
+        assert_eq!(sorted(Topology::Hex, 1, 1), vec![(0, 1), (1, 0), (1, 2), (2, 0), (2, 1), (2, 2)]);
+        assert_eq!(sorted(Topology::Hex, 1, 2), vec![(0, 1), (0, 2), (0, 3), (1, 1), (1, 3), (2, 2)]);
+        assert_eq!(sorted(Topology::HexAxial, 1, 1), vec![(0, 1), (0, 2), (1, 0), (1, 2), (2, 0), (2, 1)]);
+    }
+
+    #[test]
+    fn test_adjacency_is_symmetric() {
+        for name in Topology::NAMES {
+            let topology: Topology = name.parse().unwrap();
+            assert_eq!(topology.to_string(), name);
+            for (x, y) in (0..5).flat_map(|x| (0..4).map(move |y| (x, y))) {
+                for (nx, ny) in topology.neighbours(x, y, 5, 4) {
+                    assert!(topology.neighbours(nx, ny, 5, 4).any(|n| n == (x, y)), "{} {:?}", name, (x, y));
+                }
+            }
+        }
+    }
+}
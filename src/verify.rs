@@ -0,0 +1,219 @@
+//! Replays a solution on a grid cell by cell and reports what went wrong, if
+//! anything.
+
+use std::fmt;
+
+use crate::flood::Flood;
+use crate::free::FreeMove;
+use crate::Grid;
+
+/// State of the grid after one replayed move.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub color: u8,
+    /// Cells connected to the origin after the move, or in Free-Flood-It the
+    /// size of the region the move recoloured.
+    pub flooded: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Every move is legal and the grid ends up a single colour.
+    Valid,
+    /// A move uses a colour the grid does not have, picks a cell it does
+    /// not have, or is played on a board that is already one colour.
+    Invalid,
+    /// Every move is legal but some cells are still unflooded, or the
+    /// origins never got a common colour.
+    Incomplete,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub verdict: Verdict,
+    /// Number of moves in the solution.
+    pub moves: usize,
+    /// Moves replayed before stopping, in order.
+    pub steps: Vec<Step>,
+    /// Index of the first move that plays the colour its region already has.
+    pub first_noop: Option<usize>,
+    /// Index and colour of the first move outside the grid's colours. The
+    /// replay stops there.
+    pub illegal: Option<(usize, u8)>,
+    /// Index, row and column of the first Free-Flood-It move on a wall or
+    /// outside the grid. The replay stops there.
+    pub off_board: Option<(usize, usize, usize)>,
+    /// Index of the first Free-Flood-It move played once the board is one
+    /// colour. The replay stops there.
+    pub after_complete: Option<usize>,
+    /// Cells not connected to the origin once the replay stops, or in
+    /// Free-Flood-It those outside the largest region.
+    pub unflooded: usize,
+}
+
+impl Verdict {
+    fn of(report: &Report, complete: bool) -> Self {
+        if report.illegal.is_some() || report.off_board.is_some() || report.after_complete.is_some() {
+            Verdict::Invalid
+        } else if !complete {
+            Verdict::Incomplete
+        } else {
+            Verdict::Valid
+        }
+    }
+}
+
+/// Plays `moves` on `grid` one at a time.
+pub fn verify(grid: &Grid, moves: &[u8]) -> Report {
+    let mut replay = Flood::new(grid);
+    let mut report = Report {
+        verdict: Verdict::Valid,
+        moves: moves.len(),
+        steps: Vec::with_capacity(moves.len()),
+        first_noop: None,
+        illegal: None,
+        off_board: None,
+        after_complete: None,
+        unflooded: 0,
+    };
+
+    for (index, &color) in moves.iter().enumerate() {
+        if color as usize >= grid.colors() {
+            report.illegal = Some((index, color));
+            break;
+        }
+        if replay.is_noop(color) {
+            report.first_noop.get_or_insert(index);
+        }
+        replay.play(color);
+        report.steps.push(Step { color, flooded: replay.flooded_cells() });
+    }
+
+    report.unflooded = grid.open_cells() - replay.flooded_cells();
+    report.verdict = Verdict::of(&report, replay.is_complete());
+    report
+}
+
+/// Plays Free-Flood-It `moves` on `grid` one at a time, with
+/// `Grid::flood_fill_at`.
+pub fn verify_free(grid: &Grid, moves: &[FreeMove]) -> Report {
+    let mut replay = grid.clone();
+    let mut report = Report {
+        verdict: Verdict::Valid,
+        moves: moves.len(),
+        steps: Vec::with_capacity(moves.len()),
+        first_noop: None,
+        illegal: None,
+        off_board: None,
+        after_complete: None,
+        unflooded: 0,
+    };
+
+    for (index, m) in moves.iter().enumerate() {
+        if replay.is_complete() {
+            report.after_complete = Some(index);
+            break;
+        }
+        if m.color as usize >= grid.colors() {
+            report.illegal = Some((index, m.color));
+            break;
+        }
+        if m.row >= grid.height() || m.col >= grid.width() || grid.is_wall(m.col, m.row) {
+            report.off_board = Some((index, m.row, m.col));
+            break;
+        }
+        if replay.get(m.col, m.row) == m.color {
+            report.first_noop.get_or_insert(index);
+        }
+        let flooded = replay.flood_fill_at(m.col, m.row, m.color);
+        report.steps.push(Step { color: m.color, flooded });
+    }
+
+    let largest = crate::region::RegionGraph::new(&replay).sizes.into_iter().max().unwrap_or(0);
+    report.unflooded = grid.open_cells() - largest;
+    report.verdict = Verdict::of(&report, replay.is_complete());
+    report
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.verdict {
+            Verdict::Valid => writeln!(f, "Valid: floods the grid in {} moves", self.moves)?,
+            Verdict::Invalid => writeln!(f, "Invalid: {} moves", self.moves)?,
+            Verdict::Incomplete => writeln!(f, "Incomplete: {} moves leave {} cells unflooded", self.moves, self.unflooded)?,
+        }
+        if let Some(index) = self.first_noop {
+            writeln!(f, "Move {} is a no-op: its region already has colour {}", index + 1, self.steps[index].color)?;
+        }
+        if let Some((index, color)) = self.illegal {
+            writeln!(f, "Move {} plays colour {}, which the grid does not use", index + 1, color)?;
+        }
+        if let Some((index, row, col)) = self.off_board {
+            writeln!(f, "Move {} picks row {}, column {}, which is not a cell of the grid", index + 1, row, col)?;
+        }
+        if let Some(index) = self.after_complete {
+            writeln!(f, "Move {} is played on a board that is already one colour", index + 1)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "1,2,0,0\n0,1,1,0\n2,2,0,1\n0,0,0,1";
+
+    #[test]
+    fn test_valid_solution() {
+        let grid = Grid::from_csv(SAMPLE).unwrap();
+        let report = verify(&grid, &[2, 1, 2, 0, 1]);
+        assert_eq!(report.verdict, Verdict::Valid);
+        assert_eq!((report.moves, report.first_noop, report.unflooded), (5, None, 0));
+        assert_eq!(report.steps.last().unwrap().flooded, 16);
+    }
+
+    #[test]
+    fn test_noop_and_incomplete() {
+        let grid = Grid::from_csv(SAMPLE).unwrap();
+        let report = verify(&grid, &[2, 2, 1]);
+        assert_eq!(report.verdict, Verdict::Incomplete);
+        assert_eq!(report.first_noop, Some(1));
+        assert_eq!(report.unflooded, 16 - report.steps[2].flooded);
+    }
+
+    #[test]
+    fn test_illegal_color_stops_the_replay() {
+        let grid = Grid::from_csv(SAMPLE).unwrap();
+        let report = verify(&grid, &[2, 7, 1, 2, 0, 1]);
+        assert_eq!(report.verdict, Verdict::Invalid);
+        assert_eq!(report.illegal, Some((1, 7)));
+        assert_eq!(report.steps.len(), 1);
+    }
+
+    #[test]
+    fn test_free_moves() {
+        let grid = Grid::from_csv("0,1,0\n#,1,1").unwrap();
+        let play = |row, col, color| FreeMove { row, col, color };
+        let report = verify_free(&grid, &[play(0, 1, 0)]);
+        assert_eq!(report.verdict, Verdict::Valid);
+        assert_eq!(report.steps, [Step { color: 0, flooded: 3 }]);
+
+        let report = verify_free(&grid, &[play(0, 0, 0), play(0, 2, 1)]);
+        assert_eq!((report.verdict, report.first_noop, report.unflooded), (Verdict::Incomplete, Some(0), 1));
+        assert_eq!(verify_free(&grid, &[play(1, 0, 1)]).off_board, Some((0, 1, 0)));
+        assert_eq!(verify_free(&grid, &[play(0, 3, 1)]).verdict, Verdict::Invalid);
+
+        let report = verify_free(&grid, &[play(1, 1, 0), play(0, 0, 1)]);
+        assert_eq!((report.verdict, report.after_complete, report.steps.len()), (Verdict::Invalid, Some(1), 1));
+    }
+
+    #[test]
+    fn test_origins_of_different_colours() {
+        let grid = Grid::from_csv("start: 0,0; 1,0\n0,1\n0,1").unwrap();
+        assert_eq!(verify(&grid, &[]).verdict, Verdict::Incomplete);
+        let report = verify(&grid, &[0, 0]);
+        assert_eq!(report.verdict, Verdict::Valid);
+        assert_eq!(report.first_noop, Some(1));
+    }
+}
@@ -0,0 +1,112 @@
+# Tiny DPLL solver standing in for a real SAT solver in the tests of
+# src/sat.rs. Reads a DIMACS CNF file and answers in the SAT competition
+# format, exiting with 10 or 20 like real solvers do. Only fit for formulas
+# of a few hundred variables.
+#
+#     awk -f tiny-sat.awk problem.cnf
+
+/^c/ { next }
+/^p/ { vars = $3; next }
+{
+    for (i = 1; i <= NF; i++) {
+        if ($i == 0) {
+            clauses++
+        } else {
+            c = clauses + 1
+            lit[c, ++len[c]] = $i
+        }
+    }
+}
+
+# 1 if the literal is true, -1 if false, 0 if unassigned
+function value(l) {
+    return l > 0 ? val[l] : -val[-l]
+}
+
+function assign(l) {
+    if (l > 0) {
+        val[l] = 1
+        trail[++top] = l
+    } else {
+        val[-l] = -1
+        trail[++top] = -l
+    }
+}
+
+function undo(to) {
+    while (top > to) {
+        val[trail[top--]] = 0
+    }
+}
+
+# Unit propagation until a fixpoint; 0 on a falsified clause
+function propagate(    c, i, v, free, last, sat, changed) {
+    do {
+        changed = 0
+        for (c = 1; c <= clauses; c++) {
+            sat = 0
+            free = 0
+            for (i = 1; i <= len[c]; i++) {
+                v = value(lit[c, i])
+                if (v == 1) {
+                    sat = 1
+                    break
+                }
+                if (v == 0) {
+                    free++
+                    last = lit[c, i]
+                }
+            }
+            if (sat) {
+                continue
+            }
+            if (free == 0) {
+                return 0
+            }
+            if (free == 1) {
+                assign(last)
+                changed = 1
+            }
+        }
+    } while (changed)
+    return 1
+}
+
+END {
+    ok = propagate()
+    while (1) {
+        if (ok) {
+            for (x = 1; x <= vars && val[x] != 0; x++) {
+            }
+            if (x > vars) {
+                break
+            }
+            d++
+            mark[d] = top
+            decision[d] = x
+            flipped[d] = 0
+            assign(x)
+        } else {
+            while (d > 0 && flipped[d]) {
+                undo(mark[d])
+                d--
+            }
+            if (d == 0) {
+                print "s UNSATISFIABLE"
+                exit 20
+            }
+            undo(mark[d])
+            flipped[d] = 1
+            assign(-decision[d])
+        }
+        ok = propagate()
+    }
+
+    print "s SATISFIABLE"
+    line = "v"
+    for (x = 1; x <= vars; x++) {
+        line = line " " (val[x] * x)
+    }
+    print line " 0"
+    exit 10
+}